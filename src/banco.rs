@@ -5,29 +5,64 @@
 
 
 // Biblioteca padrão do Rust:
-use std::io::{Write, Read, Error};
+use std::io::{self, Write, Read, Error};
+use std::iter::once;
 use std::collections::HashMap;
-use std::fs::{OpenOptions, File};
-use std::path::{Path};
 
 // Resto do módulo:
 mod gerenciamento_bd;
 mod deletador;
 mod grande_inversao;
 mod ultima_insercao;
+mod formato;
+mod recuperacao;
+mod indice_primos;
+mod varredura_parcial;
+mod diretorio;
+mod pacote;
+mod organizacao_bd;
+mod manifesto;
+mod verificacao;
+mod cobertura;
+mod compactacao;
+mod codec;
+mod roda;
+mod mapeamento;
+use mapeamento::BDMapeado;
+mod exportacao;
+pub use exportacao::{exporta_bd, FormatoExportacao};
+pub use roda::{
+   converte_para_roda, converte_para_lista, modo_roda, tamanho_roda
+};
+pub use codec::Codec;
+pub use compactacao::{compacta_bd, PRIMOS_POR_REGISTRO};
+pub use cobertura::{
+   faixas_cobertas, faixas_descobertas, faixas_repetidas, insere_em_ordem,
+   prefixo_coberto
+};
+pub use verificacao::{verifica_bd, repara_bd, Rigor};
+pub use organizacao_bd::{
+   lista_backups, escolhe_backup, classifica_backups, poda_backups,
+   RETENCAO_PADRAO
+};
 pub use gerenciamento_bd::*;
 pub use ultima_insercao::{ultima_insercao_feita};
 // Usada apenas aqui, à acima exporta.
 use ultima_insercao::{atualiza_indice_de_insercao};
 pub use grande_inversao::*;
 pub use deletador::*;
+pub use formato::{
+   abre_bd, abre_bd_para_anexar, serializa_busca, reescreve_bd, define_codec
+};
+use formato::{le_registro, Cabecalho};
+use indice_primos::anexa_ao_indice;
+pub use indice_primos::{contem, n_esimo, primos_entre};
+pub use diretorio::{define_diretorio, caminho_de};
+pub use varredura_parcial::{
+   grava_varredura_parcial, varredura_parcial, descarta_varredura_parcial
+};
 
-/// Caminho e nome do BD:
-const DIR:&str = concat!(
-   env!("RUST_CODES"),
-   "/primos",
-   "/data"
-);
+/// Nome do BD(o diretório é resolvido em `diretorio`):
 const NOME_BD:&str = "banco_de_dados.dat";
 
 // Apelidos para codificação:
@@ -40,43 +75,34 @@ type BancoDeDados = Result<BD, Error>;
 
 /// Registra os `Dados` no disco.
 pub fn salva_no_bd(dados:Busca) {
-   // configurando o arquivo ao abrir.
-   let caminho = Path::new(DIR).join(NOME_BD);
-   let arquivo: Result<File, Error>  =  {
-      OpenOptions::new()
-      .append(true)
-      .open(caminho)
-   };
+   // abrindo o arquivo(já com o cabeçalho validado) no seu fim.
+   let arquivo = abre_bd_para_anexar();
    
    match arquivo {
+      // no modo roda, vai como um novo segmento, e não há índice de primos.
+      Err(_) if modo_roda() => {
+         if let Err(erro) = roda::anexa_na_roda(&dados)
+            { panic!("erro ao gravar dados no modo roda: {}", erro); }
+      },
       // se o arquivo foi aberto com sucesso.
-      Ok(mut arq) => {
-         // gravando primeiro o tanto de elementos da array.
-         let tamanho_array:u64 = dados.0.len() as u64;
-         let seus_bytes:&[u8] = &tamanho_array.to_le_bytes()[..];
-         let _ = arq.write(seus_bytes).unwrap();
-
-         // agora gravando array, valor por valor, byte por byte.
-         for valor in dados.0.iter() {
-            let seus_bytes:&[u8] = &valor.to_le_bytes()[..];
-            let _ = arq.write(seus_bytes).unwrap();
-         }
-
-         // gravando último-número-verificado...
-         let seus_bytes:&[u8] = &dados.1.to_le_bytes()[..];
-         let _ = arq.write(seus_bytes).unwrap();
+      Ok((mut arq, cabecalho)) => {
+         /* o registro inteiro(tamanho, primos, metadados e CRC) é
+          * gravado numa única escrita, e forçado ao disco antes de 
+          * atualizar o índice. Se cair no meio, o registro incompleto é
+          * descartado na próxima abertura. */
+         let registro = serializa_busca(&dados, &cabecalho);
+         let deslocamento = arq.metadata().unwrap().len();
+         arq.write_all(&registro[..]).unwrap();
+         arq.sync_data().unwrap();
 
-         // gravando tempo-de-computação(em miliseg)...
-         let seus_bytes:&[u8] = &dados.2.to_le_bytes()[..];
-         let _ = arq.write(seus_bytes).unwrap();
-
-         // gravando mini-varreduras-por-100-números...
-         let seus_bytes:&[u8] = &dados.3.to_le_bytes()[..];
-         let _ = arq.write(seus_bytes).unwrap();
+         // o índice de primos também tem que saber deste registro.
+         let tamanho = registro.len() as u64;
+         if let Err(erro) = anexa_ao_indice(deslocamento, tamanho, &dados)
+            { println!("índice de primos não atualizado: {}", erro); }
       },
       // parar programa em caso de erro.
-      Err(_) => 
-         { panic!("erro ao abrir e gravar dados no arquivo."); }
+      Err(erro) => 
+         { panic!("erro ao abrir e gravar dados no arquivo: {}", erro); }
    };
 
    /* Toda vez que for inserido com sucesso, ou não um novo dado no BD, 
@@ -98,63 +124,100 @@ pub fn carrega_bd() -> BancoDeDados {
    let mut tabela_dados = BD::new();
    let mut indice: u32 = 1;
 
-   // abrindo o arquivo do banco-de-dados...
-   let caminho = Path::new(DIR).join(NOME_BD);
-   let mut arquivo: File = {
-      OpenOptions::new()
-      .read(true)
-      .open(caminho)?
-   };
+   // no modo roda, os segmentos já voltam como buscas.
+   if modo_roda() {
+      for busca in roda::carrega_roda()? {
+         tabela_dados.insert(indice, busca);
+         indice += 1;
+      }
+      return Ok(tabela_dados);
+   }
 
-   /* enquanto não lê todos bytes do arquivo, ficar
-    * num laço infinito. Só para quando o alerta de
-    * fim de arquivo da função 'read_exact' apitar,
-    * assim "emitirá" uma quebra de laço. */
-   'leitura: loop { 
-      // array para armazenar primos escaneados.
-      match deserializa_uma_busca(&mut arquivo) {
-         Some(tupla) => {
-            //println!("{:?}", tupla.0);
-            //assert!(false);
-            tabela_dados.insert(indice, tupla);
-            // contabilizando índice para próxima inserção.
-            indice += 1
-         } None => break 'leitura,
-      };
+   /* mapeando o arquivo do banco-de-dados, um arquivo estranho ou de 
+    * versão desconhecida já é rejeitado aqui. */
+   let bd = BDMapeado::abre()?;
+
+   /* percorre todos registros direto do mapa, só a cópia de cada um para
+    * a tabela é feita. Um registro corrompido é um erro. */
+   for registro in bd.registros() {
+      tabela_dados.insert(indice, registro?.busca()?);
+      // contabilizando índice para próxima inserção.
+      indice += 1
    }
    Ok(tabela_dados)
 }
 
+/** Percorre cada registro do BD, com os seus metadados, lendo-os do 
+ disco só quando chega a sua vez(no modo roda, o arquivo, que é pequeno,
+ é lido de uma vez). Um erro, inclusive ao abrir o BD, é o último item. */
+pub fn iter_buscas() -> impl Iterator<Item = io::Result<Busca>> {
+   let buscas: Box<dyn Iterator<Item = io::Result<Busca>>> = {
+      if modo_roda() {
+         match roda::carrega_roda() {
+            Ok(buscas) => Box::new(buscas.into_iter().map(Ok)),
+            Err(erro) => Box::new(once(Err(erro)))
+         }
+      } else {
+         match BDMapeado::abre() {
+            Ok(bd) => Box::new(bd.em_buscas()),
+            Err(erro) => Box::new(once(Err(erro)))
+         }
+      }
+   };
+   buscas
+}
+
+/** Percorre todos primos do BD, na ordem dos registros, com apenas um
+ registro em memória por vez. Um erro é o último item. */
+pub fn iter_primos() -> impl Iterator<Item = io::Result<u64>> {
+   iter_buscas().flat_map(|busca| {
+      let (primos, erro) = match busca {
+         Ok((primos, ..)) => (primos, None),
+         Err(erro) => (Primos::new(), Some(Err(erro)))
+      };
+      primos.into_iter().map(Ok).chain(erro)
+   })
+}
+
 /// Coleta todos primos do banco de dados. 
+/* o 'info' já não precisa dele, só quem quiser tudo na memória. */
+#[allow(dead_code)]
 pub fn coleta_todos_primos() -> Option<Primos> {
-   // carregando todo o banco de dados...
-   match carrega_bd() {
-      // se for sucedido e abrir-lô.
-      Ok(tabela_hash_dados) => {
-         // vázio é inútil.
-         if tabela_hash_dados.is_empty() 
-            { return None; }
-         let total: u32 = tabela_hash_dados.len() as u32;
-         // array para acomodar primos.
-         let mut primos = Primos::new();
-         for indice in 1..=total {
-            // 'tupla' com dados.
-            let tupla = {
-               tabela_hash_dados
-               .get(&indice)
-               .unwrap()
-            };
-            // colocando 'fatia' na array.
-            primos.extend(&tupla.0[..]);
-         }
-         // retornando 'todos primos'.
-         Some(primos)
-      } Err(_) =>  None
+   // direto do mapa para a array, sem a tabela no meio.
+   if !modo_roda() {
+      let bd = BDMapeado::abre().ok()?;
+      // vázio é inútil.
+      bd.registros().next()?.ok()?;
+      return bd.primos().collect::<Result<Primos, Error>>().ok();
    }
+   let mut buscas = iter_buscas().peekable();
+   // vázio é inútil.
+   buscas.peek()?;
+   let mut primos = Primos::new();
+
+   for busca in buscas
+      { primos.extend(&busca.ok()?.0[..]); }
+   Some(primos)
 }
 
 /// Obtem o 'último número computado'.
 pub fn ultimo_numero_computado() -> Option<u64> {
+   // só os metadados do último registro são decodificados.
+   if !modo_roda() {
+      let ultimo = {
+         BDMapeado::abre()
+         .and_then(|bd| {
+            match bd.registros().last() {
+               Some(registro) => registro?.metadados().map(Some),
+               None => Ok(None)
+            }
+         })
+      };
+      return match ultimo {
+         Ok(metadados) => metadados.map(|m| m.0),
+         Err(erro) => { println!("{}", erro); None }
+      };
+   }
    match carrega_bd() {
       Ok(bd) => {
          // banco de dados vázio, sem valor desejável.
@@ -172,54 +235,13 @@ pub fn ultimo_numero_computado() -> Option<u64> {
 }
 
 /* Deserializa uma 'Busca' adicionado no BD como bytes. Faz apenas uma vez 
- * à cada chamada. */
-#[allow(clippy::needless_late_init)]
-pub fn deserializa_uma_busca(file:&mut File) -> Option<Busca> {
-   // Colocar bytes, temporariamente, de um valor lido(buffer).
-   let mut aux:[u8; 8] = [u8::MAX; 8]; 
-   let tamanho: u64;
-   // array para armazenar primos escaneados.
-   let mut primos:Vec<u64> = Vec::new();
-
-   /* Lendo total de valores de 64bits a gerá e colocar numa array. */
-   if file.read_exact(&mut aux).is_err()
-      { return None; }
-   else
-      { tamanho = u64::from_le_bytes(aux); }
-
-   // pegando primos 'tamanho' vezes.
-   for _ in 1..=tamanho {
-      // obtendo valor de 8-bytes.
-      match file.read_exact(&mut aux) {
-         Ok(_) => (),
-         Err(_) => return None
-      };
-      // colocando na array primo recuperado do disco.
-      primos.push(u64::from_le_bytes(aux));
-   }
-   // obtendo valor de 8-bytes.
-   match file.read_exact(&mut aux) {
-      Ok(_) => (),
-      Err(_) => return None,
-   };
-   // transformando 8-bytes array num valor de 64bits
-   let unv = u64::from_le_bytes(aux);
-   // obtendo valor de 8-bytes.
-   match file.read_exact(&mut aux) {
-      Ok(_) => (),
-      Err(_) => return None,
-   };
-   // transformando 8-bytes array num valor de 64bits
-   let ttc = u64::from_le_bytes(aux);
-   // obtendo valor de 8-bytes.
-   match file.read_exact(&mut aux) {
-      Ok(_) => (),
-      Err(_) => return None,
-   };
-   // transformando 8-bytes array num valor de 64bits
-   let mvr = u64::from_le_bytes(aux);
-   Some((primos, unv, ttc, mvr))
-}
+ * à cada chamada. Qualquer versão(e codificação) do registro é lida, o
+ * cabeçalho do arquivo diz qual. Retorna nada no fim do arquivo, e erro
+ * quando o registro está corrompido(cortado, ou com CRC-32 que não
+ * confere). */
+pub fn deserializa_uma_busca<R: Read>(leitor: &mut R, cabecalho: &Cabecalho) 
+  -> Result<Option<Busca>, Error> 
+   { le_registro(leitor, cabecalho) }
 
 
 #[cfg(test)]
@@ -232,7 +254,7 @@ mod tests {
    use utilitarios::barra_de_progresso::{ProgressoPercentual};
    
    fn zerando_bd() {
-      match remove_file(Path::new(NOME_BD)) {
+      match remove_file(caminho_de(NOME_BD)) {
          Ok(_) => { println!("removido com sucesso."); },
          Err(_) => { println!("o arquivo não existe."); }
       };
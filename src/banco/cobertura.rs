@@ -0,0 +1,253 @@
+/*! Faixas dos números já varridos pelo banco de dados. O registro não
+ guarda onde a sua varredura começou, apenas onde parou(o último número
+ verificado), então o ínicio é tirado do seu primeiro primo; entre o fim
+ de um registro e o primeiro primo do próximo há sempre uma faixa "sem
+ dono", que numa varredura contínua é só uma sequência de compostos. As
+ faixas descobertas são estas, e não custa nada varrê-las de novo: só as
+ que tiverem primos dão em algum registro.
+*/
+
+use super::compactacao::continua;
+use super::formato::{abre_bd, le_registro, reescreve_bd};
+use super::indice_primos::reconstroi_indice;
+use super::ultima_insercao::atualiza_indice_de_insercao;
+use super::{Busca, carrega_bd, iter_buscas, realiza_backup_bd};
+use std::io::{self, BufReader};
+use std::ops::RangeInclusive;
+
+/// Uma faixa de números, com ambas pontas inclusas.
+pub type Faixa = RangeInclusive<u64>;
+
+/* A faixa de um registro, dado onde parou o que vem antes dele(se há).
+ * Um registro sem primos assume que começou logo após o anterior. */
+pub(super) fn faixa_de(busca: &Busca, anterior: Option<u64>) -> Faixa {
+   let (primos, unv) = (&busca.0, busca.1);
+   let inicio = match primos.first() {
+      Some(&p) => p.min(unv),
+      None => anterior.map_or(0, |a| a.saturating_add(1)).min(unv)
+   };
+   inicio..=unv
+}
+
+/* A faixa de cada registro, na ordem do BD. */
+pub(super) fn faixas_de<'a, I>(buscas: I) -> Vec<Faixa>
+  where I: IntoIterator<Item = &'a Busca>
+{
+   let mut anterior: Option<u64> = None;
+   let mut faixas = Vec::new();
+
+   for busca in buscas {
+      faixas.push(faixa_de(busca, anterior));
+      anterior = Some(busca.1);
+   }
+   faixas
+}
+
+/** As faixas cobertas por cada registro do BD, na ordem deste. */
+pub fn faixas_cobertas() -> io::Result<Vec<Faixa>> {
+   let (arquivo, cabecalho) = abre_bd()?;
+   let mut leitor = BufReader::new(arquivo);
+   let mut buscas = Vec::new();
+
+   // só as pontas de cada registro interessam.
+   while let Some(busca) = le_registro(&mut leitor, &cabecalho)? {
+      let (primos, unv, ttc, qb) = busca;
+      let pontas = primos.first().map(|&p| vec![p]).unwrap_or_default();
+      buscas.push((pontas, unv, ttc, qb));
+   }
+   Ok(faixas_de(buscas.iter()))
+}
+
+/* As faixas dadas ordenadas, e com as que se tocam já unidas. */
+fn unidas(faixas: &[Faixa]) -> Vec<Faixa> {
+   let mut ordenadas = faixas.to_vec();
+   let mut resultado: Vec<Faixa> = Vec::with_capacity(ordenadas.len());
+   ordenadas.sort_by_key(|f| *f.start());
+
+   for faixa in ordenadas {
+      match resultado.last_mut() {
+         Some(ultima) if *faixa.start() <= ultima.end().saturating_add(1) => {
+            let fim = (*ultima.end()).max(*faixa.end());
+            *ultima = *ultima.start()..=fim;
+         } _ => resultado.push(faixa)
+      }
+   }
+   resultado
+}
+
+/** Os buracos entre as faixas cobertas, do dois(o primeiro primo) até a
+ última coberta. Um buraco só de compostos, pelo teste de primalidade
+ dado, não falta nada, então fica de fora. */
+pub fn faixas_descobertas(cobertas: &[Faixa], e_primo: fn(u64) -> bool) 
+  -> Vec<Faixa>
+{
+   let mut descobertas = Vec::new();
+   let mut proximo = 2u64;
+
+   for faixa in unidas(cobertas) {
+      if *faixa.start() > proximo {
+         let buraco = proximo..=faixa.start() - 1;
+         if buraco.clone().any(e_primo)
+            { descobertas.push(buraco); }
+      }
+      proximo = faixa.end().saturating_add(1);
+   }
+   descobertas
+}
+
+/* O fim da cobertura contínua a partir do dois, na ordem do BD(a mesma
+ * em que os primos são lidos): para no primeiro registro que não continua
+ * de onde o anterior parou, seja por um buraco, seja por estar fora de
+ * ordem. */
+fn fim_do_prefixo<'a, I>(buscas: I, e_primo: fn(u64) -> bool) -> Option<u64>
+  where I: IntoIterator<Item = &'a Busca>
+{
+   // coberto, por enquanto, até o um.
+   let mut fim = 1u64;
+
+   for busca in buscas {
+      if !continua(fim, busca, e_primo)
+         { break; }
+      fim = busca.1;
+   }
+   if fim < 2 { None } else { Some(fim) }
+}
+
+/** Até onde o BD cobre, sem nenhum buraco, os números a partir do dois;
+ nada se nem o dois é coberto. O teste de primalidade dado confere o que
+ há entre um registro e o seguinte. Serve também ao modo roda. */
+pub fn prefixo_coberto(e_primo: fn(u64) -> bool) -> io::Result<Option<u64>> {
+   let mut buscas = Vec::new();
+
+   // só as pontas de cada registro interessam.
+   for busca in iter_buscas() {
+      let (primos, unv, ttc, qb) = busca?;
+      let pontas = primos.first().map(|&p| vec![p]).unwrap_or_default();
+      buscas.push((pontas, unv, ttc, qb));
+   }
+   Ok(fim_do_prefixo(buscas.iter(), e_primo))
+}
+
+/** Faixas que foram cobertas por mais de um registro. */
+pub fn faixas_repetidas(cobertas: &[Faixa]) -> Vec<Faixa> {
+   let mut ordenadas = cobertas.to_vec();
+   let mut repetidas = Vec::new();
+   ordenadas.sort_by_key(|f| *f.start());
+   let mut alcance: Option<u64> = None;
+
+   for faixa in ordenadas {
+      if let Some(alcance) = alcance {
+         if *faixa.start() <= alcance {
+            let fim = alcance.min(*faixa.end());
+            repetidas.push(*faixa.start()..=fim);
+         }
+      }
+      alcance = Some(alcance.map_or(*faixa.end(), |a| a.max(*faixa.end())));
+   }
+   unidas(&repetidas[..])
+}
+
+/* Põe cada nova busca antes do primeiro registro que parou depois dela. */
+fn intercala(mut buscas: Vec<Busca>, novas: Vec<Busca>) -> Vec<Busca> {
+   for nova in novas {
+      let posicao = {
+         buscas.iter()
+         .position(|b| b.1 > nova.1)
+         .unwrap_or(buscas.len())
+      };
+      buscas.insert(posicao, nova);
+   }
+   buscas
+}
+
+/** Insere as buscas dadas(as varreduras das faixas descobertas) no meio
+ do BD, cada uma no seu lugar, depois de guardar um backup; refaz também
+ o índice de inserção e o de primos. Retorna o novo total de registros. */
+pub fn insere_em_ordem(novas: Vec<Busca>) -> io::Result<usize> {
+   let (_, cabecalho) = abre_bd()?;
+   let bd = carrega_bd()?;
+   let buscas: Vec<Busca> = {
+      (1..=bd.len() as u32)
+      .filter_map(|i| bd.get(&i).cloned())
+      .collect()
+   };
+
+   let caminho = realiza_backup_bd()?;
+   println!("backup de antes da inserção em {}", caminho.display());
+   let buscas = intercala(buscas, novas);
+   reescreve_bd(&buscas[..], cabecalho.ordem)?;
+   atualiza_indice_de_insercao(buscas.len() as u32);
+   reconstroi_indice()?;
+   Ok(buscas.len())
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   fn e_primo(n: u64) -> bool {
+      n >= 2 && 
+      (2..n).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+   }
+
+   #[test]
+   fn FaixasCobertasEDescobertas() {
+      let buscas: Vec<Busca> = vec![
+         (vec![2, 3, 5, 7], 8, 1, 1),
+         // o 9 e o 10 ficam de fora, mas são compostos.
+         (vec![11, 13], 16, 1, 1),
+         // lacuna: do 17 ao 36 ninguém varreu.
+         (vec![37, 41], 42, 1, 1),
+         (vec![], 46, 1, 1),
+         // o 43 já estava na faixa do anterior.
+         (vec![43, 47], 50, 1, 1)
+      ];
+      let cobertas = faixas_de(buscas.iter());
+
+      assert_eq!(cobertas, vec![2..=8, 11..=16, 37..=42, 43..=46, 43..=50]);
+      assert_eq!(faixas_descobertas(&cobertas, e_primo), vec![17..=36]);
+      assert_eq!(faixas_repetidas(&cobertas), vec![43..=46]);
+      assert!(faixas_descobertas(&[], e_primo).is_empty());
+   }
+
+   #[test]
+   fn PrefixoCobertoAteOPrimeiroBuraco() {
+      let crivo = |a: u64, b: u64| -> Vec<u64> 
+         { (a..=b).filter(|&n| e_primo(n)).collect() };
+      let buscas: Vec<Busca> = vec![
+         (vec![2, 3, 5, 7], 8, 1, 1),
+         // o 9 e o 10 são compostos, nada falta.
+         (vec![11, 13], 16, 1, 1),
+         // do 17 ao 36 ninguém varreu.
+         (vec![37, 41], 42, 1, 1)
+      ];
+      assert_eq!(fim_do_prefixo(buscas.iter(), e_primo), Some(16));
+      assert_eq!(fim_do_prefixo(&buscas[..1], e_primo), Some(8));
+      // sem o dois, não há prefixo.
+      assert_eq!(fim_do_prefixo(&buscas[2..], e_primo), None);
+      assert_eq!(fim_do_prefixo(&[], e_primo), None);
+
+      // o do meio está fora de ordem: os primos dele seriam pulados.
+      let fora_de_ordem: Vec<Busca> = vec![
+         (crivo(2, 100), 100, 1, 1),
+         (crivo(200, 300), 300, 1, 1),
+         (crivo(101, 199), 199, 1, 1)
+      ];
+      assert_eq!(fim_do_prefixo(fora_de_ordem.iter(), e_primo), Some(100));
+   }
+
+   #[test]
+   fn IntercalaNoLugarCerto() {
+      let buscas: Vec<Busca> = vec![
+         (vec![2, 3, 5, 7], 10, 1, 1),
+         (vec![37, 41], 42, 1, 1)
+      ];
+      let novas = vec![(vec![17, 19, 23, 29, 31], 36, 1, 1)];
+      let juntas = intercala(buscas, novas);
+
+      let fins: Vec<u64> = juntas.iter().map(|b| b.1).collect();
+      assert_eq!(fins, vec![10, 36, 42]);
+   }
+}
@@ -0,0 +1,468 @@
+/*! Codificações do conteúdo de um registro, a partir da versão 3 do
+ formato. O conteúdo começa com um byte que diz qual delas foi usada, então
+ registros de codificações diferentes convivem no mesmo arquivo:
+
+   0. fixa: cada valor em 8 bytes, como na versão 2;
+   1. lacunas: a diferença de cada primo para o anterior, num "varint"
+      (LEB128, 7 bits por byte), com o sinal em "zigzag"; serve para
+      qualquer lista, até fora de ordem;
+   2. comprimida: só para primos em ordem estritamente crescente. As
+      lacunas entre primos ímpares são sempre pares, então apenas a metade
+      delas é guardada, em blocos de 128, cada bloco num código de Rice
+      com o parâmetro que o deixar menor.
+
+ Os metadados(último verificado, tempo e buscas) vão sempre em "varint".
+*/
+
+use super::{Busca, ByteOrdem};
+use super::formato::{para_bytes, de_bytes};
+use std::io::{self, Error, ErrorKind};
+
+/// Lacunas de cada bloco da codificação comprimida.
+const TAMANHO_BLOCO: usize = 128;
+
+/// Como os primos de um registro são codificados.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec { Fixo, Lacunas, Comprimido }
+
+impl Codec {
+   pub fn byte(self) -> u8 {
+      match self {
+         Codec::Fixo => 0,
+         Codec::Lacunas => 1,
+         Codec::Comprimido => 2
+      }
+   }
+
+   pub fn de_byte(byte: u8) -> Option<Self> {
+      match byte {
+         0 => Some(Codec::Fixo),
+         1 => Some(Codec::Lacunas),
+         2 => Some(Codec::Comprimido),
+         _ => None
+      }
+   }
+
+   /// Pelo nome dado na linha de comando.
+   pub fn de_nome(nome: &str) -> Option<Self> {
+      match nome {
+         "fixo" => Some(Codec::Fixo),
+         "lacunas" => Some(Codec::Lacunas),
+         "comprimido" => Some(Codec::Comprimido),
+         _ => None
+      }
+   }
+}
+
+fn mal_formado() -> Error
+   { Error::new(ErrorKind::InvalidData, "registro mal formado") }
+
+fn escreve_varint(bytes: &mut Vec<u8>, mut valor: u64) {
+   while valor >= 0x80 {
+      bytes.push((valor as u8 & 0x7f) | 0x80);
+      valor >>= 7;
+   }
+   bytes.push(valor as u8);
+}
+
+fn zigzag(valor: i64) -> u64
+   { ((valor << 1) ^ (valor >> 63)) as u64 }
+
+fn dezigzag(valor: u64) -> i64
+   { (valor >> 1) as i64 ^ -((valor & 1) as i64) }
+
+/* Cursor sobre o conteúdo, lendo bytes, "varints" e bits. */
+struct Leitor<'a> { bytes: &'a [u8], posicao: usize, bit: u32 }
+
+impl<'a> Leitor<'a> {
+   fn novo(bytes: &'a [u8]) -> Self
+      { Leitor { bytes, posicao: 0, bit: 0 } }
+
+   fn oito(&mut self, ordem: ByteOrdem) -> io::Result<u64> {
+      let fim = self.posicao + 8;
+      let bytes = self.bytes.get(self.posicao..fim).ok_or_else(mal_formado)?;
+      let mut array = [0u8; 8];
+      array.copy_from_slice(bytes);
+      self.posicao = fim;
+      Ok(de_bytes(array, ordem))
+   }
+
+   fn byte(&mut self) -> io::Result<u8> {
+      let byte = *self.bytes.get(self.posicao).ok_or_else(mal_formado)?;
+      self.posicao += 1;
+      Ok(byte)
+   }
+
+   fn varint(&mut self) -> io::Result<u64> {
+      let mut valor = 0u64;
+
+      for deslocamento in (0..64).step_by(7) {
+         let byte = self.byte()?;
+         valor |= ((byte & 0x7f) as u64) << deslocamento;
+         if byte & 0x80 == 0
+            { return Ok(valor); }
+      }
+      Err(mal_formado())
+   }
+
+   fn le_bit(&mut self) -> io::Result<u64> {
+      let byte = *self.bytes.get(self.posicao).ok_or_else(mal_formado)?;
+      let valor = (byte >> (7 - self.bit)) & 1;
+
+      self.bit += 1;
+      if self.bit == 8
+         { self.bit = 0; self.posicao += 1; }
+      Ok(valor as u64)
+   }
+
+   // o próximo bloco sempre começa num byte novo.
+   fn alinha(&mut self) {
+      if self.bit > 0
+         { self.bit = 0; self.posicao += 1; }
+   }
+
+   fn rice(&mut self, k: u32) -> io::Result<u64> {
+      let mut quociente = 0u64;
+      while self.le_bit()? == 1
+         { quociente += 1; }
+      let mut resto = 0u64;
+      for _ in 0..k
+         { resto = (resto << 1) | self.le_bit()?; }
+      quociente.checked_shl(k)
+      .filter(|q| q >> k == quociente)
+      .map(|q| q | resto)
+      .ok_or_else(mal_formado)
+   }
+
+   fn chegou_ao_fim(&self) -> bool
+      { self.posicao == self.bytes.len() && self.bit == 0 }
+}
+
+/* Escreve bits, do mais para o menos significativo de cada byte. */
+struct Bits<'a> { bytes: &'a mut Vec<u8>, bit: u32 }
+
+impl<'a> Bits<'a> {
+   fn escreve(&mut self, valor: u64) {
+      if self.bit == 0
+         { self.bytes.push(0); }
+      *self.bytes.last_mut().unwrap() |= (valor as u8) << (7 - self.bit);
+      self.bit = (self.bit + 1) % 8;
+   }
+
+   fn rice(&mut self, valor: u64, k: u32) {
+      for _ in 0..(valor >> k)
+         { self.escreve(1); }
+      self.escreve(0);
+      for i in (0..k).rev()
+         { self.escreve((valor >> i) & 1); }
+   }
+}
+
+/* O parâmetro de Rice que deixa o bloco menor. */
+fn melhor_parametro(valores: &[u64]) -> u32 {
+   (0..64u32).min_by_key(|&k| {
+      valores.iter()
+      .map(|&v| (v >> k) as u128 + 1 + k as u128)
+      .sum::<u128>()
+   }).unwrap()
+}
+
+/* Só primos em ordem estritamente crescente, todos ímpares(tirando um dois
+ * logo no começo), vão na codificação comprimida. */
+fn cabe_na_comprimida(primos: &[u64]) -> bool {
+   let impares = match primos.first() {
+      Some(2) => &primos[1..],
+      _ => primos
+   };
+   impares.iter().all(|p| p % 2 == 1)
+   && impares.windows(2).all(|par| par[0] < par[1])
+}
+
+fn codifica_lacunas(primos: &[u64], bytes: &mut Vec<u8>) {
+   let mut anterior = 0u64;
+   escreve_varint(bytes, primos.len() as u64);
+
+   for &p in primos {
+      escreve_varint(bytes, zigzag(p.wrapping_sub(anterior) as i64));
+      anterior = p;
+   }
+}
+
+fn codifica_comprimido(primos: &[u64], bytes: &mut Vec<u8>) {
+   let dois = primos.first() == Some(&2);
+   let impares = if dois { &primos[1..] } else { primos };
+   escreve_varint(bytes, primos.len() as u64);
+   bytes.push(dois as u8);
+
+   if let Some(&primeiro) = impares.first() {
+      escreve_varint(bytes, primeiro);
+      // metade da lacuna, menos um, assim começa no zero.
+      let metades: Vec<u64> = {
+         impares.windows(2)
+         .map(|par| (par[1] - par[0]) / 2 - 1)
+         .collect()
+      };
+      for bloco in metades.chunks(TAMANHO_BLOCO) {
+         let k = melhor_parametro(bloco);
+         bytes.push(k as u8);
+         let mut bits = Bits { bytes: &mut *bytes, bit: 0 };
+         for &valor in bloco
+            { bits.rice(valor, k); }
+      }
+   }
+}
+
+/** Codifica o conteúdo do registro, já com o byte da codificação. Se a
+ comprimida não servir para os primos dados, é usada a de lacunas. */
+pub fn codifica(dados: &Busca, codec: Codec, ordem: ByteOrdem) -> Vec<u8> {
+   let codec = {
+      if codec == Codec::Comprimido && !cabe_na_comprimida(&dados.0[..])
+         { Codec::Lacunas }
+      else
+         { codec }
+   };
+   let mut bytes = vec![codec.byte()];
+
+   match codec {
+      Codec::Fixo => {
+         bytes.extend(&para_bytes(dados.0.len() as u64, ordem)[..]);
+         for &valor in dados.0.iter().chain([dados.1, dados.2, dados.3].iter())
+            { bytes.extend(&para_bytes(valor, ordem)[..]); }
+         return bytes;
+      }
+      Codec::Lacunas => codifica_lacunas(&dados.0[..], &mut bytes),
+      Codec::Comprimido => codifica_comprimido(&dados.0[..], &mut bytes)
+   };
+   for &valor in [dados.1, dados.2, dados.3].iter()
+      { escreve_varint(&mut bytes, valor); }
+   bytes
+}
+
+/* Onde a leitura dos primos de um registro está. */
+enum Estado {
+   // cada valor em 8 bytes, na ordem dada.
+   Fixo(ByteOrdem),
+   Lacunas { anterior: u64 },
+   /* falta o dois(se houver), e então o primeiro ímpar; depois, os
+    * blocos, de parâmetro 'k', cada um com 'no_bloco' lacunas ainda. */
+   Comprimido { dois: bool, primeiro: bool, atual: u64, k: u32, no_bloco: usize }
+}
+
+/** Os primos de um registro, decodificados um a um, direto dos bytes do
+ seu conteúdo(sem copiar nada). Os metadados vêm depois de todos eles. */
+pub struct PrimosCodificados<'a> {
+   leitor: Leitor<'a>,
+   restantes: u64,
+   estado: Estado
+}
+
+impl<'a> PrimosCodificados<'a> {
+   /** Começa a ler o conteúdo(já verificado pelo CRC) de um registro, da
+    versão 2(sempre fixo, e sem o byte da codificação) ou da 3. */
+   pub fn novos(bytes: &'a [u8], versao: u16, ordem: ByteOrdem)
+     -> io::Result<Self>
+   {
+      let (codec, resto) = match versao {
+         0..=2 => (Codec::Fixo, bytes),
+         _ => {
+            let (&byte, resto) = bytes.split_first().ok_or_else(mal_formado)?;
+            (Codec::de_byte(byte).ok_or_else(mal_formado)?, resto)
+         }
+      };
+      let mut leitor = Leitor::novo(resto);
+
+      let (restantes, estado) = match codec {
+         Codec::Fixo => {
+            let qtd = leitor.oito(ordem)?;
+            // o resto tem que ser exatamente os primos e os metadados.
+            let valores = (resto.len() / 8 - 1) as u64;
+            if resto.len() % 8 != 0 || qtd.checked_add(3) != Some(valores)
+               { return Err(mal_formado()); }
+            (qtd, Estado::Fixo(ordem))
+         }
+         Codec::Lacunas =>
+            { (leitor.varint()?, Estado::Lacunas { anterior: 0 }) }
+         Codec::Comprimido => {
+            let qtd = leitor.varint()?;
+            let dois = match leitor.byte()? {
+               0 => false,
+               1 => true,
+               _ => return Err(mal_formado())
+            };
+            if qtd < dois as u64
+               { return Err(mal_formado()); }
+            let estado = Estado::Comprimido {
+               dois, primeiro: true, atual: 0, k: 0, no_bloco: 0
+            };
+            (qtd, estado)
+         }
+      };
+      Ok(PrimosCodificados { leitor, restantes, estado })
+   }
+
+   /* Capacidade segura para guardar os que faltam: cada primo ocupa ao
+    * menos um bit, um conteúdo corrompido não pede memória à toa. */
+   fn capacidade(&self) -> usize {
+      let bits = 8 * (self.leitor.bytes.len() - self.leitor.posicao) as u64;
+      self.restantes.min(bits + 1) as usize
+   }
+
+   fn proximo(&mut self) -> io::Result<u64> {
+      let leitor = &mut self.leitor;
+
+      match &mut self.estado {
+         Estado::Fixo(ordem) => leitor.oito(*ordem),
+         Estado::Lacunas { anterior } => {
+            *anterior = anterior.wrapping_add(dezigzag(leitor.varint()?) as u64);
+            Ok(*anterior)
+         }
+         Estado::Comprimido { dois, primeiro, atual, k, no_bloco } => {
+            if *dois
+               { *dois = false; return Ok(2); }
+            if *primeiro {
+               *primeiro = false;
+               *atual = leitor.varint()?;
+               return Ok(*atual);
+            }
+            if *no_bloco == 0 {
+               *k = leitor.byte()? as u32;
+               if *k >= 64
+                  { return Err(mal_formado()); }
+               *no_bloco = self.restantes.min(TAMANHO_BLOCO as u64) as usize;
+            }
+            let metade = leitor.rice(*k)?.checked_add(1).ok_or_else(mal_formado)?;
+            *atual = {
+               metade.checked_mul(2)
+               .and_then(|lacuna| atual.checked_add(lacuna))
+               .ok_or_else(mal_formado)?
+            };
+            *no_bloco -= 1;
+            // o próximo bloco sempre começa num byte novo.
+            if *no_bloco == 0
+               { leitor.alinha(); }
+            Ok(*atual)
+         }
+      }
+   }
+
+   /** Os metadados(último verificado, tempo e buscas), pulando os primos
+    que ainda não foram lidos. */
+   pub fn metadados(mut self) -> io::Result<(u64, u64, u64)> {
+      for primo in self.by_ref()
+         { primo?; }
+      let leitor = &mut self.leitor;
+      let metadados = match self.estado {
+         Estado::Fixo(ordem) =>
+            (leitor.oito(ordem)?, leitor.oito(ordem)?, leitor.oito(ordem)?),
+         _ => (leitor.varint()?, leitor.varint()?, leitor.varint()?)
+      };
+
+      if !leitor.chegou_ao_fim()
+         { return Err(mal_formado()); }
+      Ok(metadados)
+   }
+}
+
+impl<'a> Iterator for PrimosCodificados<'a> {
+   type Item = io::Result<u64>;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      if self.restantes == 0
+         { return None; }
+      let primo = self.proximo();
+      // depois de um erro, não há mais o que ler.
+      self.restantes = if primo.is_ok() { self.restantes - 1 } else { 0 };
+      Some(primo)
+   }
+}
+
+/** Decodifica o conteúdo(já verificado pelo CRC) de um registro, da
+ versão 2(sempre fixo, e sem o byte da codificação) ou da 3. */
+pub fn decodifica(bytes: &[u8], versao: u16, ordem: ByteOrdem)
+  -> io::Result<Busca>
+{
+   let mut codificados = PrimosCodificados::novos(bytes, versao, ordem)?;
+   let mut primos = Vec::with_capacity(codificados.capacidade());
+
+   for primo in codificados.by_ref()
+      { primos.push(primo?); }
+   let (unv, ttc, qb) = codificados.metadados()?;
+   Ok((primos, unv, ttc, qb))
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   // crivo simples, o do motor não é visível daqui.
+   fn primos_ate(n: usize) -> Vec<u64> {
+      let mut marcas = vec![true; n + 1];
+      (2..=n).filter(|&i| {
+         if marcas[i] {
+            (i * i..=n).step_by(i).for_each(|j| marcas[j] = false);
+         }
+         marcas[i]
+      }).map(|i| i as u64).collect()
+   }
+
+   fn ida_e_volta(dados: &Busca, codec: Codec) -> usize {
+      for ordem in [ByteOrdem::LittleEndian, ByteOrdem::BigEndian] {
+         let bytes = codifica(dados, codec, ordem);
+         assert_eq!(&decodifica(&bytes[..], 3, ordem).unwrap(), dados);
+      }
+      codifica(dados, codec, ByteOrdem::LittleEndian).len()
+   }
+
+   #[test]
+   fn TodasCodificacoesIdaEVolta() {
+      let casos: Vec<Busca> = vec![
+         (primos_ate(10_000), 10_000, 3, 100),
+         (primos_ate(10_000)[1..].to_vec(), 10_003, 0, 1),
+         (vec![], 7, 0, 0),
+         (vec![2], 2, 1, 1),
+         (vec![18_446_744_073_709_551_557], u64::MAX, u64::MAX, 0),
+         // fora de ordem e repetidos: a comprimida não serve.
+         (vec![97, 5, 5, u64::MAX, 0, 2], 100, 1, 1)
+      ];
+      for dados in casos.iter() {
+         for codec in [Codec::Fixo, Codec::Lacunas, Codec::Comprimido]
+            { ida_e_volta(dados, codec); }
+      }
+      // versão 2: fixo, e sem o byte da codificação.
+      let dados = &casos[0];
+      let bytes = codifica(dados, Codec::Fixo, ByteOrdem::BigEndian);
+      assert_eq!(
+         &decodifica(&bytes[1..], 2, ByteOrdem::BigEndian).unwrap(), dados
+      );
+   }
+
+   #[test]
+   fn ComprimidaEMenor() {
+      let dados = (primos_ate(1_000_000), 1_000_000, 1, 1);
+      let fixo = ida_e_volta(&dados, Codec::Fixo);
+      let lacunas = ida_e_volta(&dados, Codec::Lacunas);
+      let comprimido = ida_e_volta(&dados, Codec::Comprimido);
+
+      println!("fixo: {}, lacunas: {}, comprimido: {}", fixo, lacunas, comprimido);
+      assert!(lacunas * 7 < fixo);
+      assert!(comprimido * 3 < lacunas * 2);
+   }
+
+   #[test]
+   fn ConteudoEstragadoERejeitado() {
+      let dados = (primos_ate(1000), 1000, 1, 1);
+      for codec in [Codec::Lacunas, Codec::Comprimido] {
+         let bytes = codifica(&dados, codec, ByteOrdem::LittleEndian);
+         for corte in [1, 2, bytes.len() / 2, bytes.len() - 1] {
+            let resultado = decodifica(&bytes[..corte], 3, ByteOrdem::LittleEndian);
+            assert!(resultado.map_or(true, |d| d != dados));
+         }
+         let mut sobra = bytes.clone();
+         sobra.push(0);
+         assert!(decodifica(&sobra[..], 3, ByteOrdem::LittleEndian).is_err());
+      }
+      assert!(decodifica(&[9, 0, 0, 0, 0], 3, ByteOrdem::LittleEndian).is_err());
+   }
+}
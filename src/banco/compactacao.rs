@@ -0,0 +1,146 @@
+/*! Compactação do banco de dados: cada varredura vira um registro, e as
+ pequenas(o 'procura' acha uns 932 primos) se acumulam aos milhares. Aqui,
+ registros consecutivos são fundidos em outros maiores, até um limite de
+ primos por registro, somando o tempo e a quantia de buscas de todos eles;
+ o último número verificado é o do último fundido.
+
+ Também é por aqui que um BD de versão antiga(ou de outra codificação) é
+ convertido: mesmo sem nada a fundir, ele é reescrito no formato atual.
+*/
+
+use super::formato::{abre_bd, reescreve_bd, codec_padrao, VERSAO_ATUAL};
+use super::indice_primos::reconstroi_indice;
+use super::ultima_insercao::atualiza_indice_de_insercao;
+use super::{Busca, carrega_bd, realiza_backup_bd};
+use std::io;
+
+/// Limite padrão de primos de um registro compactado.
+pub const PRIMOS_POR_REGISTRO: usize = 100_000;
+
+/* Se o registro 'seguinte' continua de onde parou o que terminou em
+ * 'unv': parou depois dele, e o seu ínicio(ver `cobertura`) é logo depois,
+ * ou entre os dois há só compostos, a faixa "sem dono" de uma varredura
+ * contínua. */
+pub(super) fn continua(unv: u64, seguinte: &Busca, e_primo: fn(u64) -> bool)
+  -> bool
+{
+   if seguinte.1 < unv
+      { return false; }
+   let proximo = unv.saturating_add(1);
+   let inicio = match seguinte.0.first() {
+      Some(&p) => p.min(seguinte.1),
+      None => proximo
+   };
+   inicio <= proximo || !(proximo..inicio).any(e_primo)
+}
+
+/* Funde os registros consecutivos, sem passar do limite de primos em
+ * cada um. Só funde se o seguinte parou depois do atual, e começa logo
+ * onde ele parou; assim um BD fora de ordem, ou com lacunas, não tem os
+ * seus problemas escondidos(ver 'verifica-bd' e 'preenche'). */
+fn funde(buscas: Vec<Busca>, limite: usize, e_primo: fn(u64) -> bool)
+  -> Vec<Busca>
+{
+   let mut fundidas: Vec<Busca> = Vec::new();
+
+   for busca in buscas {
+      match fundidas.last_mut() {
+         Some(atual) if atual.0.len() + busca.0.len() <= limite
+           && continua(atual.1, &busca, e_primo) =>
+         {
+            atual.0.extend(&busca.0[..]);
+            atual.1 = busca.1;
+            atual.2 += busca.2;
+            atual.3 += busca.3;
+         } _ => fundidas.push(busca)
+      }
+   }
+   fundidas
+}
+
+/** Compacta o banco de dados, depois de guardar um backup dele; refaz o
+ índice de inserção e o de primos. O teste de primalidade dado confere o
+ que há entre um registro e o seguinte. Retorna a quantia de registros
+ antes e depois. */
+pub fn compacta_bd(limite: usize, e_primo: fn(u64) -> bool)
+  -> io::Result<(usize, usize)>
+{
+   let (_, cabecalho) = abre_bd()?;
+   let bd = carrega_bd()?;
+   let total = bd.len();
+   let buscas: Vec<Busca> = {
+      (1..=total as u32)
+      .filter_map(|i| bd.get(&i).cloned())
+      .collect()
+   };
+   drop(bd);
+
+   let compactadas = funde(buscas, limite, e_primo);
+   if compactadas.len() == total && !cabecalho.desatualizado()
+      { return Ok((total, total)); }
+   let caminho = realiza_backup_bd()?;
+   println!("backup de antes da compactação em {}", caminho.display());
+
+   reescreve_bd(&compactadas[..], cabecalho.ordem)?;
+   if cabecalho.desatualizado() {
+      println!(
+         "convertido da versão {}({:?}) para a {}({:?}).",
+         cabecalho.versao, cabecalho.codec, VERSAO_ATUAL, codec_padrao()
+      );
+   }
+   atualiza_indice_de_insercao(compactadas.len() as u32);
+   reconstroi_indice()?;
+   Ok((total, compactadas.len()))
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   // suficiente para os números pequenos dos testes.
+   fn e_primo(n: u64) -> bool {
+      n >= 2 && 
+      (2..n).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+   }
+
+   #[test]
+   fn FundeAteOLimite() {
+      let buscas: Vec<Busca> = vec![
+         (vec![2, 3, 5], 6, 1, 10),
+         (vec![7], 10, 2, 20),
+         (vec![11, 13], 16, 3, 30),
+         (vec![17, 19, 23], 28, 4, 40),
+         // parou antes do anterior, não é fundido.
+         (vec![], 20, 5, 50)
+      ];
+      assert_eq!(funde(buscas.clone(), 4, e_primo), vec![
+         (vec![2, 3, 5, 7], 10, 3, 30),
+         (vec![11, 13], 16, 3, 30),
+         (vec![17, 19, 23], 28, 4, 40),
+         (vec![], 20, 5, 50)
+      ]);
+      assert_eq!(funde(buscas.clone(), 100, e_primo), vec![
+         (vec![2, 3, 5, 7, 11, 13, 17, 19, 23], 28, 10, 100),
+         (vec![], 20, 5, 50)
+      ]);
+      assert!(funde(Vec::new(), 100, e_primo).is_empty());
+   }
+
+   #[test]
+   fn LacunaSobreviveACompactacao() {
+      let buscas: Vec<Busca> = vec![
+         (vec![2, 3, 5, 7], 8, 1, 1),
+         // o 9 e o 10 são compostos, então continua de onde parou.
+         (vec![11], 12, 1, 1),
+         // falta o 13: faixa nunca varrida, não pode sumir.
+         (vec![17, 19], 20, 1, 1),
+         (vec![], 22, 1, 1)
+      ];
+      assert_eq!(funde(buscas, 100, e_primo), vec![
+         (vec![2, 3, 5, 7, 11], 12, 2, 2),
+         (vec![17, 19], 22, 2, 2)
+      ]);
+   }
+}
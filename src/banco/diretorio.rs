@@ -0,0 +1,73 @@
+/*! Onde ficam todos os arquivos de dados do programa: o banco de dados, o
+ índice de última inserção, o índice de primos, os backups, e etc. O
+ diretório é resolvido em tempo de execução, na seguinte ordem:
+
+   1. o dado via `--dados <dir>`;
+   2. a variável de ambiente `PRIMOS_DATA`;
+   3. `$RUST_CODES/primos/data`, onde sempre ficou, se existir;
+   4. o diretório de dados do XDG(`$XDG_DATA_HOME/primos`, ou então
+      `~/.local/share/primos`).
+
+ Uma vez resolvido, o diretório não muda mais durante a execução.
+*/
+
+use std::env::var_os;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::{Once, OnceLock};
+
+// Nome da variável de ambiente que aponta o diretório de dados.
+const VARIAVEL_DADOS: &str = "PRIMOS_DATA";
+static DIRETORIO: OnceLock<PathBuf> = OnceLock::new();
+// O diretório só é criado(se preciso) no primeiro acesso.
+static CRIACAO: Once = Once::new();
+
+/* Diretório de dados do XDG para o programa. */
+fn diretorio_xdg() -> PathBuf {
+   match var_os("XDG_DATA_HOME") {
+      Some(base) if !base.is_empty() =>
+         { Path::new(&base).join("primos") }
+      _ => {
+         let casa = var_os("HOME").unwrap_or_default();
+         Path::new(&casa).join(".local/share/primos")
+      }
+   }
+}
+
+/* Resolve o diretório a partir do ambiente(ver a ordem no topo). */
+fn resolve_diretorio() -> PathBuf {
+   if let Some(caminho) = var_os(VARIAVEL_DADOS) {
+      if !caminho.is_empty()
+         { return PathBuf::from(caminho); }
+   }
+   if let Some(raiz) = var_os("RUST_CODES") {
+      let antigo = Path::new(&raiz).join("primos/data");
+      if antigo.is_dir()
+         { return antigo; }
+   }
+   diretorio_xdg()
+}
+
+/** Fixa o diretório de dados(o dado via `--dados`). Tem que ser chamado
+ antes de qualquer acesso aos dados; retorna falso se já era tarde. */
+pub fn define_diretorio(caminho: PathBuf) -> bool
+   { DIRETORIO.set(caminho).is_ok() }
+
+/** O diretório de dados, já criado se ainda não existia. */
+pub fn diretorio() -> &'static Path {
+   let caminho = DIRETORIO.get_or_init(resolve_diretorio);
+
+   CRIACAO.call_once(|| {
+      if let Err(erro) = create_dir_all(caminho) {
+         panic!(
+            "não foi possível criar o diretório de dados {}: {}",
+            caminho.display(), erro
+         );
+      }
+   });
+   caminho
+}
+
+/** Caminho de um arquivo(ou subdiretório) dentro do diretório de dados. */
+pub fn caminho_de(nome: &str) -> PathBuf
+   { diretorio().join(nome) }
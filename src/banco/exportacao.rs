@@ -0,0 +1,264 @@
+/*! Exportação do banco de dados para formatos de texto: os primos, um a
+ um, ou os registros(as varreduras), com as suas faixas e metadados. Tudo
+ é lido e escrito registro por registro, então o BD nunca fica inteiro na
+ memória, e a saída pode ser um arquivo ou a saída padrão.
+*/
+
+use super::cobertura::{faixa_de, Faixa};
+use super::formato::abre_bd;
+use super::roda::modo_roda;
+use super::{deserializa_uma_busca, iter_buscas, Busca};
+use std::io::{self, BufReader, Write};
+use std::iter::{from_fn, once};
+
+/// Os formatos de texto em que o BD pode ser exportado.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatoExportacao { Txt, Csv, Json, Ndjson }
+
+impl FormatoExportacao {
+   /** O formato pelo nome dado na linha de comando. */
+   pub fn de_nome(nome: &str) -> Option<Self> {
+      match nome {
+         "txt" => Some(FormatoExportacao::Txt),
+         "csv" => Some(FormatoExportacao::Csv),
+         "json" => Some(FormatoExportacao::Json),
+         "ndjson" => Some(FormatoExportacao::Ndjson),
+         _ => None
+      }
+   }
+}
+
+/* Os registros do BD, na ordem, lidos um por vez do arquivo. Para depois
+ * de um erro. */
+fn registros_do_bd() -> Box<dyn Iterator<Item = io::Result<Busca>>> {
+   // a roda não tem registros no arquivo, é montada de uma vez.
+   if modo_roda()
+      { return Box::new(iter_buscas()); }
+   let (arquivo, cabecalho) = match abre_bd() {
+      Ok(aberto) => aberto,
+      Err(erro) => return Box::new(once(Err(erro)))
+   };
+   let mut leitor = BufReader::new(arquivo);
+   let mut acabou = false;
+
+   Box::new(from_fn(move || {
+      if acabou
+         { return None; }
+      match deserializa_uma_busca(&mut leitor, &cabecalho) {
+         Ok(busca) => busca.map(Ok),
+         Err(erro) => { acabou = true; Some(Err(erro)) }
+      }
+   }))
+}
+
+/* Escreve os primos da faixa; retorna quantos foram. */
+fn exporta_primos<W, I>(saida: &mut W, registros: I,
+  formato: FormatoExportacao, faixa: &Faixa) -> io::Result<u64>
+  where W: Write, I: Iterator<Item = io::Result<Busca>>
+{
+   let mut total: u64 = 0;
+
+   match formato {
+      FormatoExportacao::Csv => writeln!(saida, "primo")?,
+      FormatoExportacao::Json => write!(saida, "[")?,
+      _ => ()
+   };
+   // o último primo escrito.
+   let mut anterior: Option<u64> = None;
+
+   for busca in registros {
+      for primo in busca?.0 {
+         /* o registro é crescente, então passar do fim é acabar com ele;
+          * mas não com o BD, que pode ter registros fora de ordem. */
+         if primo > *faixa.end()
+            { break; }
+         // repetido(na borda de dois registros), ou fora de ordem.
+         if primo < *faixa.start() || anterior.is_some_and(|a| primo <= a)
+            { continue; }
+         anterior = Some(primo);
+         match formato {
+            FormatoExportacao::Json if total > 0 =>
+               { write!(saida, ",\n{}", primo)?; }
+            FormatoExportacao::Json =>
+               { write!(saida, "\n{}", primo)?; }
+            // cada número já é um JSON por si só.
+            _ => writeln!(saida, "{}", primo)?
+         };
+         total += 1;
+      }
+   }
+   if formato == FormatoExportacao::Json
+      { writeln!(saida, "\n]")?; }
+   Ok(total)
+}
+
+/* Escreve os registros que tocam a faixa, com a faixa que cada um cobre
+ * (ver `cobertura`); retorna quantos foram. */
+fn exporta_registros<W, I>(saida: &mut W, registros: I,
+  formato: FormatoExportacao, faixa: &Faixa) -> io::Result<u64>
+  where W: Write, I: Iterator<Item = io::Result<Busca>>
+{
+   let campos = "inicio,ultimo_verificado,tempo,buscas,primos";
+   let mut anterior: Option<u64> = None;
+   let mut total: u64 = 0;
+
+   match formato {
+      FormatoExportacao::Csv => writeln!(saida, "{}", campos)?,
+      FormatoExportacao::Json => write!(saida, "[")?,
+      _ => ()
+   };
+   for busca in registros {
+      let busca = busca?;
+      let inicio = *faixa_de(&busca, anterior).start();
+      let (primos, unv, ttc, qb) = busca;
+      anterior = Some(unv);
+
+      if inicio > *faixa.end()
+         { break; }
+      if unv < *faixa.start()
+         { continue; }
+      let qtd = primos.len();
+      let valores: Vec<String> = {
+         [inicio, unv, ttc, qb, qtd as u64]
+         .iter().map(u64::to_string).collect()
+      };
+      let objeto = format!(
+         "{{\"inicio\": {}, \"ultimo_verificado\": {}, \"tempo\": {}, \
+         \"buscas\": {}, \"primos\": {}}}", inicio, unv, ttc, qb, qtd
+      );
+      match formato {
+         FormatoExportacao::Txt =>
+            { writeln!(saida, "{}", valores.join("\t"))?; }
+         FormatoExportacao::Csv =>
+            { writeln!(saida, "{}", valores.join(","))?; }
+         FormatoExportacao::Json if total > 0 =>
+            { write!(saida, ",\n   {}", objeto)?; }
+         FormatoExportacao::Json =>
+            { write!(saida, "\n   {}", objeto)?; }
+         FormatoExportacao::Ndjson =>
+            { writeln!(saida, "{}", objeto)?; }
+      };
+      total += 1;
+   }
+   if formato == FormatoExportacao::Json
+      { writeln!(saida, "\n]")?; }
+   Ok(total)
+}
+
+/**
+ Exporta, no formato dado, os primos do BD que estão na faixa dada, ou,
+ se `por_registro`, os registros que a tocam. Retorna a quantia de primos
+ (ou de registros) escritos. Um erro de leitura no meio do BD interrompe
+ a exportação, e o que já foi escrito fica como está.
+*/
+pub fn exporta_bd<W: Write>(saida: &mut W, formato: FormatoExportacao,
+  faixa: Faixa, por_registro: bool) -> io::Result<u64>
+{
+   let registros = registros_do_bd();
+   let total = {
+      if por_registro
+         { exporta_registros(saida, registros, formato, &faixa)? }
+      else
+         { exporta_primos(saida, registros, formato, &faixa)? }
+   };
+   saida.flush()?;
+   Ok(total)
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   fn buscas() -> Vec<io::Result<Busca>> {
+      vec![
+         Ok((vec![2, 3, 5, 7], 10, 1, 1)),
+         Ok((vec![], 12, 2, 2)),
+         Ok((vec![13, 17, 19, 23], 24, 3, 3))
+      ]
+   }
+
+   fn exporta(formato: FormatoExportacao, faixa: Faixa, por_registro: bool)
+     -> String
+   {
+      let mut saida: Vec<u8> = Vec::new();
+      let registros = buscas().into_iter();
+      let exportador = {
+         if por_registro
+            { exporta_registros::<Vec<u8>, _> }
+         else
+            { exporta_primos::<Vec<u8>, _> }
+      };
+      exportador(&mut saida, registros, formato, &faixa).unwrap();
+      String::from_utf8(saida).unwrap()
+   }
+
+   #[test]
+   fn PrimosEmCadaFormato() {
+      assert_eq!(
+         exporta(FormatoExportacao::Txt, 0..=u64::MAX, false),
+         "2\n3\n5\n7\n13\n17\n19\n23\n"
+      );
+      assert_eq!(
+         exporta(FormatoExportacao::Csv, 4..=14, false),
+         "primo\n5\n7\n13\n"
+      );
+      assert_eq!(
+         exporta(FormatoExportacao::Json, 17..=100, false),
+         "[\n17,\n19,\n23\n]\n"
+      );
+      assert_eq!(exporta(FormatoExportacao::Json, 8..=12, false), "[\n]\n");
+      assert_eq!(exporta(FormatoExportacao::Ndjson, 0..=2, false), "2\n");
+   }
+
+   #[test]
+   fn RegistrosComAsSuasFaixas() {
+      assert_eq!(
+         exporta(FormatoExportacao::Csv, 0..=u64::MAX, true),
+         "inicio,ultimo_verificado,tempo,buscas,primos\n\
+         2,10,1,1,4\n11,12,2,2,0\n13,24,3,3,4\n"
+      );
+      assert_eq!(
+         exporta(FormatoExportacao::Txt, 11..=11, true),
+         "11\t12\t2\t2\t0\n"
+      );
+      assert_eq!(
+         exporta(FormatoExportacao::Ndjson, 20..=30, true),
+         "{\"inicio\": 13, \"ultimo_verificado\": 24, \"tempo\": 3, \
+         \"buscas\": 3, \"primos\": 4}\n"
+      );
+   }
+
+   #[test]
+   fn PrimoRepetidoNaBordaSaiUmaVez() {
+      let mut saida: Vec<u8> = Vec::new();
+      let registros: Vec<io::Result<Busca>> = vec![
+         Ok((vec![2, 3, 5, 7], 7, 1, 1)),
+         // o 7 de novo, como a varredura antiga fazia.
+         Ok((vec![7, 11, 13], 14, 1, 1)),
+         // fora de ordem: o que passa do fim não encerra a exportação.
+         Ok((vec![23, 29], 30, 1, 1)),
+         Ok((vec![17, 19], 22, 1, 1))
+      ];
+      let total = exporta_primos(
+         &mut saida, registros.into_iter(),
+         FormatoExportacao::Txt, &(5..=20)
+      ).unwrap();
+      assert_eq!(total, 6);
+      assert_eq!(saida, b"5\n7\n11\n13\n17\n19\n");
+   }
+
+   #[test]
+   fn ErroDeLeituraInterrompe() {
+      let mut saida: Vec<u8> = Vec::new();
+      let erro = io::Error::new(io::ErrorKind::InvalidData, "estragado");
+      let registros = vec![Ok((vec![2, 3], 4, 1, 1)), Err(erro)];
+      let resultado = exporta_primos(
+         &mut saida, registros.into_iter(),
+         FormatoExportacao::Txt, &(0..=u64::MAX)
+      );
+      assert!(resultado.is_err());
+      assert_eq!(saida, b"2\n3\n");
+   }
+}
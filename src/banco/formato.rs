@@ -0,0 +1,569 @@
+/*! Formato versionado do arquivo "banco de dados". O arquivo começa com um
+ cabeçalho de 16 bytes:
+
+   * número mágico "PRIMOSBD"(8 bytes);
+   * versão do formato(u16, sempre little-endian);
+   * ordem dos bytes dos registros(0 é 'BigEndian', 255 'LittleEndian');
+   * codificação dos registros anexados(ver `codec`; só na versão 3);
+   * quatro bytes reservados.
+
+ Cada registro(uma `Busca`) vem prefixado pelo seu tamanho em bytes, e é
+ sucedido pelo CRC-32 do seu conteúdo. Assim, arquivos estranhos ou
+ registros corrompidos são rejeitados, ao invés de lidos errado. Na versão
+ 2, o conteúdo tem cada valor em 8 bytes; na 3, começa com um byte dizendo
+ a sua codificação, e ambas versões são lidas. Um arquivo da versão 2 segue
+ recebendo registros da versão 2, até ser reescrito(o 'compacta' faz isso).
+
+ O formato antigo(versão 1), sem cabeçalho, é convertido no lugar pelo
+ migrador, na primeira vez que o banco de dados é aberto.
+*/
+
+use super::{Busca, ByteOrdem, NOME_BD, caminho_de};
+use super::codec::{self, Codec};
+use super::recuperacao::recupera_bd;
+use super::roda::modo_roda;
+use std::fs::{File, OpenOptions, rename, copy};
+use std::io::{self, Read, Write, Seek, SeekFrom, Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Once;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Identificação de todo arquivo de banco de dados.
+const MAGICO: [u8; 8] = *b"PRIMOSBD";
+/// Versão do formato que é gravada atualmente.
+pub const VERSAO_ATUAL: u16 = 3;
+/// Total de bytes do cabeçalho.
+pub const TAMANHO_CABECALHO: u64 = 16;
+// Arquivo que guardava a ordem de bytes no formato antigo.
+const ORDEM_LEGADA: &str = "byte-order.dat";
+// A passagem de recuperação só é feita na primeira abertura.
+static RECUPERACAO: Once = Once::new();
+// Codificação dos arquivos novos ou reescritos(ver `define_codec`).
+static CODEC_PADRAO: AtomicU8 = AtomicU8::new(2);
+
+/** Troca a codificação usada nos bancos de dados criados ou reescritos
+ daqui em diante. A padrão é a comprimida. */
+pub fn define_codec(codec: Codec)
+   { CODEC_PADRAO.store(codec.byte(), Ordering::Relaxed); }
+
+/// Codificação usada nos bancos de dados criados ou reescritos.
+pub fn codec_padrao() -> Codec {
+   let byte = CODEC_PADRAO.load(Ordering::Relaxed);
+   Codec::de_byte(byte).unwrap_or(Codec::Comprimido)
+}
+
+/// Metadados lidos no ínicio do arquivo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cabecalho {
+   pub versao: u16,
+   pub ordem: ByteOrdem,
+   pub codec: Codec
+}
+
+impl Cabecalho {
+   pub fn novo(ordem: ByteOrdem) -> Self
+      { Cabecalho { versao: VERSAO_ATUAL, ordem, codec: codec_padrao() } }
+
+   /// Se ainda é preciso reescrever o arquivo para ficar como um novo.
+   pub fn desatualizado(&self) -> bool
+      { self.versao < VERSAO_ATUAL || self.codec != codec_padrao() }
+
+   pub fn serializa(&self) -> [u8; TAMANHO_CABECALHO as usize] {
+      let mut bytes = [0u8; TAMANHO_CABECALHO as usize];
+      bytes[0..8].copy_from_slice(&MAGICO[..]);
+      bytes[8..10].copy_from_slice(&self.versao.to_le_bytes()[..]);
+      bytes[10] = match self.ordem {
+         ByteOrdem::BigEndian => 0,
+         ByteOrdem::LittleEndian => 255
+      };
+      if self.versao >= 3
+         { bytes[11] = self.codec.byte(); }
+      bytes
+   }
+
+   pub fn deserializa(bytes: &[u8]) -> io::Result<Self> {
+      if bytes.len() < TAMANHO_CABECALHO as usize || bytes[0..8] != MAGICO {
+         let msg = "não é um arquivo de banco de dados de primos";
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+      let versao = u16::from_le_bytes([bytes[8], bytes[9]]);
+
+      if !(2..=VERSAO_ATUAL).contains(&versao) {
+         let msg = format!("versão {} do formato não suportada", versao);
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+      let ordem = match bytes[10] {
+         0 => ByteOrdem::BigEndian,
+         255 => ByteOrdem::LittleEndian,
+         _ => {
+            let msg = "ordem de bytes desconhecida no cabeçalho";
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+         }
+      };
+      // a versão 2 só conhecia a codificação fixa.
+      let codec = match versao {
+         2 => Some(Codec::Fixo),
+         _ => Codec::de_byte(bytes[11])
+      };
+      let codec = codec.ok_or_else(|| {
+         let msg = "codificação desconhecida no cabeçalho";
+         Error::new(ErrorKind::InvalidData, msg)
+      })?;
+      Ok(Cabecalho { versao, ordem, codec })
+   }
+}
+
+/* Tabela do CRC-32(polinômio IEEE, o mesmo do zip), computada uma vez em
+ * tempo de compilação. */
+const TABELA_CRC: [u32; 256] = {
+   let mut tabela = [0u32; 256];
+   let mut n = 0;
+
+   while n < 256 {
+      let mut c = n as u32;
+      let mut k = 0;
+      while k < 8 {
+         c = if c & 1 == 1 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+         k += 1;
+      }
+      tabela[n] = c;
+      n += 1;
+   }
+   tabela
+};
+
+/// Soma de verificação CRC-32 dos bytes dados.
+pub fn crc32(bytes: &[u8]) -> u32 {
+   let mut crc = u32::MAX;
+   for &b in bytes
+      { crc = TABELA_CRC[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8); }
+   !crc
+}
+
+/// Bytes de um valor de 64-bits, seguindo a ordem dada.
+pub fn para_bytes(valor: u64, ordem: ByteOrdem) -> [u8; 8] {
+   match ordem {
+      ByteOrdem::LittleEndian => valor.to_le_bytes(),
+      ByteOrdem::BigEndian => valor.to_be_bytes()
+   }
+}
+
+/// Valor de 64-bits dos bytes dados, seguindo a ordem dada.
+pub fn de_bytes(bytes: [u8; 8], ordem: ByteOrdem) -> u64 {
+   match ordem {
+      ByteOrdem::LittleEndian => u64::from_le_bytes(bytes),
+      ByteOrdem::BigEndian => u64::from_be_bytes(bytes)
+   }
+}
+
+/* Bytes do CRC-32, também seguindo a ordem dada. */
+fn crc_para_bytes(crc: u32, ordem: ByteOrdem) -> [u8; 4] {
+   match ordem {
+      ByteOrdem::LittleEndian => crc.to_le_bytes(),
+      ByteOrdem::BigEndian => crc.to_be_bytes()
+   }
+}
+
+pub(super) fn crc_de_bytes(bytes: [u8; 4], ordem: ByteOrdem) -> u32 {
+   match ordem {
+      ByteOrdem::LittleEndian => u32::from_le_bytes(bytes),
+      ByteOrdem::BigEndian => u32::from_be_bytes(bytes)
+   }
+}
+
+/* Conteúdo de um registro: a quantia de primos, os primos, e os três
+ * metadados(último verificado, tempo e varreduras). Na versão 2 é sempre
+ * o fixo, e sem o byte da codificação. */
+fn serializa_conteudo(dados: &Busca, cabecalho: &Cabecalho) -> Vec<u8> {
+   let ordem = cabecalho.ordem;
+
+   if cabecalho.versao < 3
+      { codec::codifica(dados, Codec::Fixo, ordem).split_off(1) }
+   else
+      { codec::codifica(dados, cabecalho.codec, ordem) }
+}
+
+/** Registro completo, já pronto para ir ao disco(ou a um arquivo com o
+ cabeçalho dado): tamanho do conteúdo, o conteúdo, e o CRC-32 deste. */
+pub fn serializa_busca(dados: &Busca, cabecalho: &Cabecalho) -> Vec<u8> {
+   let ordem = cabecalho.ordem;
+   let conteudo = serializa_conteudo(dados, cabecalho);
+   let crc = crc32(&conteudo[..]);
+   let mut registro = Vec::with_capacity(conteudo.len() + 12);
+
+   registro.extend(&para_bytes(conteudo.len() as u64, ordem)[..]);
+   registro.extend(&conteudo[..]);
+   registro.extend(&crc_para_bytes(crc, ordem)[..]);
+   registro
+}
+
+/* Lê 8 bytes, porém diferencia o fim de arquivo limpo(nenhum byte lido)
+ * de um valor cortado pela metade. */
+fn le_oito_bytes<R: Read>(leitor: &mut R) -> io::Result<Option<[u8; 8]>> {
+   let mut buffer = [0u8; 8];
+   let mut lido = 0;
+
+   while lido < 8 {
+      match leitor.read(&mut buffer[lido..]) {
+         Ok(0) => break,
+         Ok(n) => lido += n,
+         Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+         Err(erro) => return Err(erro)
+      };
+   }
+
+   match lido {
+      0 => Ok(None),
+      8 => Ok(Some(buffer)),
+      _ => Err(Error::new(ErrorKind::UnexpectedEof, "registro incompleto"))
+   }
+}
+
+/** Lê um registro à partir da posição atual do leitor, de um arquivo com
+ o cabeçalho dado. Retorna nada no fim do arquivo, e erro se o registro
+ estiver cortado ou não bater com seu CRC-32. */
+pub fn le_registro<R: Read>(leitor: &mut R, cabecalho: &Cabecalho)
+  -> io::Result<Option<Busca>>
+{
+   let ordem = cabecalho.ordem;
+   let tamanho = match le_oito_bytes(leitor)? {
+      Some(bytes) => de_bytes(bytes, ordem),
+      None => return Ok(None)
+   };
+   let mut conteudo = Vec::new();
+   let lido = leitor.by_ref().take(tamanho).read_to_end(&mut conteudo)?;
+   let mut crc = [0u8; 4];
+
+   if (lido as u64) < tamanho
+      { return Err(Error::new(ErrorKind::UnexpectedEof, "registro incompleto")); }
+   leitor.read_exact(&mut crc)?;
+
+   if crc_de_bytes(crc, ordem) != crc32(&conteudo[..]) {
+      let msg = "CRC-32 do registro não confere, dados corrompidos";
+      return Err(Error::new(ErrorKind::InvalidData, msg));
+   }
+   codec::decodifica(&conteudo[..], cabecalho.versao, ordem).map(Some)
+}
+
+/** Confere um banco de dados inteiro, já em memória(o de um backup, por
+ exemplo): cabeçalho válido, e todos registros íntegros, sem sobras no
+ final. Retorna a quantia de registros. */
+pub fn valida_bd(bytes: &[u8]) -> io::Result<usize> {
+   if bytes.is_empty()
+      { return Ok(0); }
+   let cabecalho = Cabecalho::deserializa(bytes)?;
+   let mut leitor = &bytes[TAMANHO_CABECALHO as usize..];
+   let mut registros = 0;
+
+   while le_registro(&mut leitor, &cabecalho)?.is_some()
+      { registros += 1; }
+   Ok(registros)
+}
+
+/// Caminho do arquivo de banco de dados.
+pub fn caminho_bd() -> PathBuf
+   { caminho_de(NOME_BD) }
+
+/* Ordem dos bytes registrada pelo formato antigo, num arquivo separado. Se
+ * não houver tal, o padrão sempre foi o 'LittleEndian'. */
+fn ordem_legada() -> ByteOrdem {
+   let caminho = caminho_de(ORDEM_LEGADA);
+   let mut byte = [255u8; 1];
+
+   if let Ok(mut arquivo) = File::open(caminho)
+      { let _ = arquivo.read(&mut byte[..]); }
+   if byte[0] == 0
+      { ByteOrdem::BigEndian }
+   else
+      { ByteOrdem::LittleEndian }
+}
+
+/* Lê todo arquivo no formato antigo. Tem que consumir o arquivo inteiro
+ * sem sobras, caso contrário não é um banco de dados(ou está corrompido).*/
+fn le_formato_legado(bytes: &[u8], ordem: ByteOrdem) -> io::Result<Vec<Busca>> {
+   let erro = || {
+      let msg = "arquivo estranho ou corrompido, não dá para migrá-lo";
+      Error::new(ErrorKind::InvalidData, msg)
+   };
+   if bytes.len() % 8 != 0
+      { return Err(erro()); }
+   let mut valores = {
+      bytes.chunks_exact(8)
+      .map(|c| { let mut a = [0u8; 8]; a.copy_from_slice(c); a })
+      .map(|a| de_bytes(a, ordem))
+   };
+   let mut buscas = Vec::<Busca>::new();
+
+   while let Some(qtd) = valores.next() {
+      let primos: Vec<u64> = valores.by_ref().take(qtd as usize).collect();
+      let metadados: Vec<u64> = valores.by_ref().take(3).collect();
+
+      if primos.len() as u64 != qtd || metadados.len() != 3
+         { return Err(erro()); }
+      buscas.push((primos, metadados[0], metadados[1], metadados[2]));
+   }
+   Ok(buscas)
+}
+
+/** Regrava, de forma atômica, todo o banco de dados com as buscas dadas,
+ já na versão e codificação atuais. Escreve num arquivo temporário, força
+ ele ao disco, e só então troca com o atual. */
+pub fn reescreve_bd(buscas: &[Busca], ordem: ByteOrdem) -> io::Result<()> {
+   let caminho = caminho_bd();
+   let temporario = caminho.with_extension("dat.novo");
+   let mut arquivo = {
+      OpenOptions::new()
+      .create(true).write(true).truncate(true)
+      .open(&temporario)?
+   };
+
+   let cabecalho = Cabecalho::novo(ordem);
+
+   arquivo.write_all(&cabecalho.serializa()[..])?;
+   for busca in buscas
+      { arquivo.write_all(&serializa_busca(busca, &cabecalho)[..])?; }
+   arquivo.sync_all()?;
+   drop(arquivo);
+
+   rename(&temporario, &caminho)
+}
+
+/** Converte, no lugar, um banco de dados no formato antigo(sem cabeçalho)
+ para o formato atual. Uma cópia do antigo fica guardada com a extensão
+ ".v1". Retorna a quantia de registros migrados. */
+pub fn migra_bd() -> io::Result<usize> {
+   let caminho = caminho_bd();
+   let mut bytes = Vec::new();
+   File::open(&caminho)?.read_to_end(&mut bytes)?;
+
+   if bytes.len() >= 8 && bytes[0..8] == MAGICO
+      { return Ok(0); }
+
+   let ordem = ordem_legada();
+   let buscas = le_formato_legado(&bytes[..], ordem)?;
+   let copia = caminho.with_extension("dat.v1");
+
+   // guarda o antigo, só depois converte.
+   copy(&caminho, &copia)?;
+   reescreve_bd(&buscas[..], ordem)?;
+
+   println!(
+      "banco de dados migrado para a versão {} ({} registros)",
+      VERSAO_ATUAL, buscas.len()
+   );
+   Ok(buscas.len())
+}
+
+/* Verifica se o arquivo já está no formato atual, caso não, e não seja
+ * vazio, migra ele. */
+fn garante_formato_atual(arquivo: &mut File) -> io::Result<()> {
+   let mut inicio = [0u8; 8];
+   let tamanho = arquivo.metadata()?.len();
+
+   arquivo.seek(SeekFrom::Start(0))?;
+   if tamanho >= 8 {
+      arquivo.read_exact(&mut inicio)?;
+      if inicio == MAGICO
+         { return Ok(()); }
+   }
+   if tamanho > 0
+      { migra_bd()?; }
+   Ok(())
+}
+
+/* O BD no modo roda não tem a lista que o resto espera. */
+fn so_na_lista() -> Error {
+   let msg = "o BD está no modo roda, volte para a lista com 'converte lista'";
+   Error::other(msg)
+}
+
+/** Abre o banco de dados para leitura, validando o cabeçalho. O arquivo é
+ retornado já posicionado no primeiro registro. Um banco de dados vázio é
+ tratado como se tivesse apenas o cabeçalho padrão. Na primeira abertura
+ também é feita a recuperação de uma possível gravação interrompida. */
+pub fn abre_bd() -> io::Result<(File, Cabecalho)> {
+   let caminho = caminho_bd();
+   if modo_roda()
+      { return Err(so_na_lista()); }
+   let mut arquivo = File::open(&caminho)?;
+
+   garante_formato_atual(&mut arquivo)?;
+   RECUPERACAO.call_once(|| {
+      if let Err(erro) = recupera_bd()
+         { println!("não foi possível recuperar o BD: {}", erro); }
+   });
+   // reabre, pois a migração(ou recuperação) troca o arquivo.
+   let mut arquivo = File::open(&caminho)?;
+   let mut bytes = [0u8; TAMANHO_CABECALHO as usize];
+
+   if arquivo.metadata()?.len() == 0
+      { return Ok((arquivo, Cabecalho::novo(ByteOrdem::LittleEndian))); }
+   arquivo.read_exact(&mut bytes)?;
+   Ok((arquivo, Cabecalho::deserializa(&bytes[..])?))
+}
+
+/** Abre o banco de dados para anexar registros, criando ele, e gravando o
+ cabeçalho, caso ainda não exista. */
+pub fn abre_bd_para_anexar() -> io::Result<(File, Cabecalho)> {
+   let caminho = caminho_bd();
+   // senão, criaria uma lista vazia ao lado da roda.
+   if modo_roda()
+      { return Err(so_na_lista()); }
+   let mut arquivo = {
+      OpenOptions::new()
+      .read(true).write(true).create(true)
+      .open(&caminho)?
+   };
+
+   if arquivo.metadata()?.len() == 0 {
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+      arquivo.write_all(&cabecalho.serializa()[..])?;
+      arquivo.sync_all()?;
+      return Ok((arquivo, cabecalho));
+   }
+   drop(arquivo);
+
+   // valida(ou migra) o que já existe, só então abre no fim.
+   let (_, cabecalho) = abre_bd()?;
+   let arquivo = OpenOptions::new().append(true).open(&caminho)?;
+   Ok((arquivo, cabecalho))
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use std::io::Cursor;
+
+   #[test]
+   fn Crc32ValoresConhecidos() {
+      assert_eq!(crc32(b""), 0);
+      assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+   }
+
+   #[test]
+   fn CabecalhoIdaEVolta() {
+      for ordem in [ByteOrdem::LittleEndian, ByteOrdem::BigEndian] {
+         let cabecalho = Cabecalho::novo(ordem);
+         let bytes = cabecalho.serializa();
+         assert_eq!(Cabecalho::deserializa(&bytes[..]).unwrap(), cabecalho);
+      }
+   }
+
+   #[test]
+   fn CabecalhoDaVersaoDois() {
+      let mut bytes = Cabecalho::novo(ByteOrdem::BigEndian).serializa();
+      bytes[8] = 2;
+      // na versão 2 o byte da codificação era reservado.
+      bytes[11] = 0xAB;
+      let cabecalho = Cabecalho::deserializa(&bytes[..]).unwrap();
+      assert_eq!(cabecalho.codec, Codec::Fixo);
+      assert!(cabecalho.desatualizado());
+      // já na 3, é conferido.
+      bytes[8] = 3;
+      assert!(Cabecalho::deserializa(&bytes[..]).is_err());
+   }
+
+   #[test]
+   fn RejeitaArquivoEstranho() {
+      let mut bytes = Cabecalho::novo(ByteOrdem::LittleEndian).serializa();
+      bytes[0] = b'X';
+      assert!(Cabecalho::deserializa(&bytes[..]).is_err());
+      // versão do futuro.
+      let mut bytes = Cabecalho::novo(ByteOrdem::LittleEndian).serializa();
+      bytes[8] = 99;
+      assert!(Cabecalho::deserializa(&bytes[..]).is_err());
+   }
+
+   /* Cabeçalhos de todas as combinações de versão, ordem e codificação. */
+   fn todos_cabecalhos() -> Vec<Cabecalho> {
+      let mut todos = Vec::new();
+      for ordem in [ByteOrdem::LittleEndian, ByteOrdem::BigEndian] {
+         todos.push(Cabecalho { versao: 2, ordem, codec: Codec::Fixo });
+         for codec in [Codec::Fixo, Codec::Lacunas, Codec::Comprimido]
+            { todos.push(Cabecalho { versao: 3, ordem, codec }); }
+      }
+      todos
+   }
+
+   #[test]
+   fn RegistroIdaEVolta() {
+      let busca: Busca = (vec![101, 103, 107, 109, 113], 120, 3, 1);
+
+      for cabecalho in todos_cabecalhos() {
+         let mut bytes = serializa_busca(&busca, &cabecalho);
+         // dois registros seguidos.
+         bytes.extend(serializa_busca(&busca, &cabecalho));
+         let mut leitor = Cursor::new(bytes);
+         let mut le = || le_registro(&mut leitor, &cabecalho).unwrap();
+
+         assert_eq!(le(), Some(busca.clone()));
+         assert_eq!(le(), Some(busca.clone()));
+         assert_eq!(le(), None);
+      }
+   }
+
+   #[test]
+   fn RegistroDaVersaoDoisNaoMuda() {
+      // o mesmo que a versão 2 sempre gravou: 8 bytes para cada valor.
+      let busca: Busca = (vec![2, 3], 4, 5, 6);
+      let ordem = ByteOrdem::LittleEndian;
+      let cabecalho = Cabecalho { versao: 2, ordem, codec: Codec::Fixo };
+      let mut esperado = 48u64.to_le_bytes().to_vec();
+      let mut conteudo = Vec::new();
+      for v in [2u64, 2, 3, 4, 5, 6]
+         { conteudo.extend(&v.to_le_bytes()[..]); }
+      esperado.extend(&conteudo[..]);
+      esperado.extend(&crc32(&conteudo[..]).to_le_bytes()[..]);
+
+      assert_eq!(serializa_busca(&busca, &cabecalho), esperado);
+   }
+
+   #[test]
+   fn RegistroCorrompidoERejeitado() {
+      let busca: Busca = (vec![2, 3, 5, 7], 10, 0, 1);
+
+      for cabecalho in todos_cabecalhos() {
+         let mut bytes = serializa_busca(&busca, &cabecalho);
+         // troca um bit do conteúdo.
+         bytes[10] ^= 0b0000_0100;
+         let mut leitor = Cursor::new(bytes.clone());
+         assert!(le_registro(&mut leitor, &cabecalho).is_err());
+         // registro cortado no meio.
+         bytes[10] ^= 0b0000_0100;
+         bytes.truncate(bytes.len() - 6);
+         let mut leitor = Cursor::new(bytes);
+         assert!(le_registro(&mut leitor, &cabecalho).is_err());
+      }
+   }
+
+   #[test]
+   fn FormatoLegado() {
+      let ordem = ByteOrdem::LittleEndian;
+      let mut bytes = Vec::<u8>::new();
+      for v in [2u64, 11, 13, 15, 100, 7]
+         { bytes.extend(&v.to_le_bytes()[..]); }
+      for v in [1u64, 17, 20, 1, 1]
+         { bytes.extend(&v.to_le_bytes()[..]); }
+
+      let buscas = le_formato_legado(&bytes[..], ordem).unwrap();
+      assert_eq!(buscas, vec![(vec![11, 13], 15, 100, 7), (vec![17], 20, 1, 1)]);
+      // sobras no fim, não é um banco de dados válido.
+      bytes.extend(&9u64.to_le_bytes()[..]);
+      assert!(le_formato_legado(&bytes[..], ordem).is_err());
+   }
+
+   #[test]
+   fn ValidaBDInteiro() {
+      let cabecalho = Cabecalho::novo(ByteOrdem::BigEndian);
+      let mut bytes = cabecalho.serializa().to_vec();
+      bytes.extend(serializa_busca(&(vec![2, 3, 5], 6, 1, 1), &cabecalho));
+      bytes.extend(serializa_busca(&(vec![7, 11], 12, 1, 1), &cabecalho));
+
+      assert_eq!(valida_bd(&bytes[..]).unwrap(), 2);
+      assert_eq!(valida_bd(&[]).unwrap(), 0);
+      assert!(valida_bd(&bytes[..bytes.len() - 1]).is_err());
+      assert!(valida_bd(&bytes[8..]).is_err());
+   }
+}
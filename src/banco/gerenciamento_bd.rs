@@ -6,24 +6,35 @@
 
 // biblioteca padrão do Rust.
 use std::{
-   path::{PathBuf},
+   io::{self, ErrorKind, Error, Write},
+   path::{Path, PathBuf},
    time::{SystemTime, UNIX_EPOCH},
-   fs::{create_dir},
-   process::Command,
-   env::temp_dir,
+   fs::{create_dir_all, read, remove_file, rename, OpenOptions},
+};
+// próprio módulo.
+use super::{
+   caminho_de, formato::valida_bd,
+   roda::{modo_roda, valida_roda, NOME_RODA},
+   organizacao_bd::{poda_backups, RETENCAO_PADRAO},
+   manifesto::{
+      impressao_digital, registra_no_manifesto, pacote_com_impressao,
+      impressao_registrada
+   },
+   pacote::{escreve_pacote, le_pacote, Embrulho}
 };
-// próprio caixote.
-use crate::{computa_caminho};
 
-// nome do atual BD.
-const CAMINHO_BD:&str = "data/banco_de_dados.dat";
-const CAMINHO_UI:&str = "data/ultima_insercao.dat";
-const CAMINHO_REGISTROS:&str = "data/registros.dat";
+// nome do atual BD(todos no diretório de dados).
+const CAMINHO_BD:&str = "banco_de_dados.dat";
+const CAMINHO_UI:&str = "ultima_insercao.dat";
+const CAMINHO_ORDEM:&str = "byte-order.dat";
 // diretório dos backup's.
-const TODOS_BACKUPS:&str = "data/backups/";
+const TODOS_BACKUPS:&str = "backups/";
 const NOME_ORIGINAL_ARQ:&str = "backup_bd";
+/* derivados do BD, que são refeitos sozinhos, e não podem sobreviver a
+ * uma troca do BD. */
+const DERIVADOS: [&str; 2] = ["indice_primos.dat", "varredura_parcial.dat"];
 
-// Computa um 'nome+ID' para dá ao archive criado.
+// Computa um 'nome+ID' para dá ao pacote criado, já no dir. dos backups.
 #[allow(clippy::needless_return)]
 fn cria_nome_id() -> PathBuf {
    let tempo_id: u128 = {
@@ -34,61 +45,176 @@ fn cria_nome_id() -> PathBuf {
    };
 
    let nome_arquivo: String = format!(
-      "{}_{}.zip",
+      "{}_{}.pacote",
       NOME_ORIGINAL_ARQ,
       tempo_id
    );
 
-   let mut caminho = PathBuf::new();
-   caminho.push(temp_dir());
+   let mut caminho = caminho_de(TODOS_BACKUPS);
    caminho.push(nome_arquivo);
 
    return caminho;
 }
 
+/* Lê o arquivo dado do diretório de dados para ser embrulhado. O BD tem
+ * que existir, os demais são opcionais. */
+fn embrulha(nome: &str, obrigatorio: bool) -> io::Result<Option<Embrulho>> {
+   match read(caminho_de(nome)) {
+      Ok(conteudo) => 
+         { Ok(Some((nome.to_string(), conteudo))) }
+      Err(ref erro) if erro.kind() == ErrorKind::NotFound && !obrigatorio =>
+         { Ok(None) }
+      Err(erro) => Err(erro)
+   }
+}
+
+/* Um backup já existente com exatamente os mesmos arquivos. A impressão
+ * digital só acha o candidato, o conteúdo é comparado por inteiro. */
+fn backup_identico(impressao: u64, arquivos: &[Embrulho]) -> Option<PathBuf> {
+   let caminho = pacote_com_impressao(impressao)?;
+
+   match le_pacote(&caminho) {
+      Ok((_, existentes)) if existentes == arquivos => Some(caminho),
+      _ => None
+   }
+}
+
 /** 
  Realiza um novo backup do BD, ele salva tanto o aglomerado de dados 
- gerados, como o atalho para acessar o BD de forma mais específica.  
+ gerados, como o atalho para acessar o BD de forma mais específica. Tudo
+ vai num único pacote(com CRCs), no diretório dos backups; é retornado o
+ caminho deste. Se nada mudou desde um backup já feito, ele é reaproveitado
+ (e fica como o mais recente) em vez de gravar outro igual. Em seguida, os
+ backups antigos que a política de retenção não mantém são podados.
 */
-pub fn realiza_backup_bd() { 
-   // transformando slice-strings em 'PathBuf'.
-   let caminho_bd = computa_caminho(CAMINHO_BD);
-   let caminho_registros = computa_caminho(CAMINHO_REGISTROS);
-   let caminho_ui = computa_caminho(CAMINHO_UI);
-   let todos_backups = computa_caminho(TODOS_BACKUPS);
-
+pub fn realiza_backup_bd() -> io::Result<PathBuf> { 
+   let todos_backups = caminho_de(TODOS_BACKUPS);
    // criando diretório, se necessário...
-   match create_dir(todos_backups.clone()) {
-      Ok(_) => (),
-      Err(_) =>
-         { println!("caminho já existe."); }
+   create_dir_all(&todos_backups)?;
+
+   // no modo roda, é o arquivo dela que não pode faltar.
+   let roda = modo_roda();
+   let mut arquivos: Vec<Embrulho> = Vec::with_capacity(4);
+   for (nome, obrigatorio) in [(CAMINHO_BD, !roda), (NOME_RODA, roda),
+     (CAMINHO_UI, false), (CAMINHO_ORDEM, false)]
+   {
+      if let Some(embrulho) = embrulha(nome, obrigatorio)?
+         { arquivos.push(embrulho); }
+   }
+
+   let impressao = impressao_digital(&arquivos[..]);
+   if let Some(existente) = backup_identico(impressao, &arquivos[..]) {
+      // para a retenção, ele é o backup mais recente.
+      OpenOptions::new().write(true).open(&existente)?
+      .set_modified(SystemTime::now())?;
+      return Ok(existente);
+   }
+
+   let destino = cria_nome_id();
+   escreve_pacote(&destino, &arquivos[..])?;
+   let nome = destino.file_name().unwrap().to_string_lossy();
+   registra_no_manifesto(impressao, &nome)?;
+
+   // o backup já está salvo, uma falha na poda não o desfaz.
+   match poda_backups(&RETENCAO_PADRAO, false) {
+      Ok(podados) => {
+         for b in podados 
+            { println!("backup antigo podado: '{}'", b.nome); }
+      } Err(erro) => 
+         { println!("não foi possível podar os backups: {}", erro); }
+   };
+   Ok(destino)
+}
+
+
+/* Troca, de forma atômica, o arquivo do diretório de dados pelo conteúdo
+ * dado. */
+fn substitui(nome: &str, conteudo: &[u8]) -> io::Result<()> {
+   let destino = caminho_de(nome);
+   let temporario = destino.with_extension("restauracao");
+   let mut arquivo = {
+      OpenOptions::new()
+      .create(true).write(true).truncate(true)
+      .open(&temporario)?
    };
 
-   // comando de "compactação".
-   let mut zipa_bd = Command::new("zip");
-   let nome_zip = cria_nome_id();
-   zipa_bd.args([
-      nome_zip.to_str().unwrap(),
-      caminho_bd.to_str().unwrap(),
-      caminho_registros.to_str().unwrap(),
-      caminho_ui.to_str().unwrap()
-   ]);
-   
-   // comando de "movimentação". 
-   let mut move_zipados = Command::new("mv");
-   move_zipados.arg("-v");
-   move_zipados.arg(nome_zip.to_str().unwrap());
-   move_zipados.arg(todos_backups.to_str().unwrap());
-
-   // compactando os arquivos de registros.
-   match zipa_bd.spawn() {
-      Ok(mut processo) => 
-         { processo.wait().unwrap(); }
-      Err(_) => 
-         { panic!("não foi possível compactar!"); }
+   arquivo.write_all(conteudo)?;
+   arquivo.sync_all()?;
+   drop(arquivo);
+   rename(&temporario, &destino)
+}
+
+/* Remove o arquivo do diretório de dados, se existir. */
+fn remove_se_existir(nome: &str) -> io::Result<()> {
+   match remove_file(caminho_de(nome)) {
+      Err(ref erro) if erro.kind() == ErrorKind::NotFound => Ok(()),
+      resultado => resultado
+   }
+}
+
+/**
+ Restaura o BD a partir do pacote de backup dado. O pacote é todo conferido
+ antes(CRCs, e cada registro do BD), e o BD atual vai para um novo backup,
+ cujo caminho é retornado(nada se não havia BD). Os arquivos que não
+ estiverem no pacote, assim como os derivados do BD, são apagados, para
+ que sejam refeitos a partir do BD restaurado.
+*/
+pub fn restaura_backup(origem: &Path) -> io::Result<Option<PathBuf>> {
+   let (_, arquivos) = le_pacote(origem)?;
+   let nome = origem.file_name().map(|n| n.to_string_lossy());
+
+   // o que está no manifesto tem que bater com o que foi lido.
+   if let Some(nome) = nome {
+      let registrada = impressao_registrada(&nome)?;
+      if registrada.is_some_and(|i| i != impressao_digital(&arquivos)) {
+         let msg = "o backup não confere com o manifesto";
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+   }
+   let conhecidos = [CAMINHO_BD, NOME_RODA, CAMINHO_UI, CAMINHO_ORDEM];
+
+   // só aceita os arquivos do BD, nada de caminhos estranhos.
+   for (nome, _) in arquivos.iter() {
+      if !conhecidos.contains(&nome.as_str()) {
+         let msg = format!("arquivo '{}' inesperado no backup", nome);
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+   }
+   // o BD, seja a lista ou a roda.
+   let principal = |(nome, _): &&Embrulho| {
+      nome == CAMINHO_BD || nome == NOME_RODA
+   };
+   let (nome_bd, bd) = match arquivos.iter().find(principal) {
+      Some((nome, conteudo)) => (nome.as_str(), conteudo),
+      None => {
+         let msg = "o backup não tem o banco de dados";
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+   };
+   let registros = {
+      if nome_bd == NOME_RODA
+         { valida_roda(&bd[..])? }
+      else
+         { valida_bd(&bd[..])? }
    };
-   // então move tais archives.
-   move_zipados.spawn().unwrap();
+   println!("backup válido, com {} registros.", registros);
+
+   // antes de tudo, guarda o que há agora.
+   let anterior = match realiza_backup_bd() {
+      Ok(caminho) => Some(caminho),
+      Err(ref erro) if erro.kind() == ErrorKind::NotFound => None,
+      Err(erro) => return Err(erro)
+   };
+
+   for nome in DERIVADOS.iter().chain(conhecidos.iter()) {
+      if !arquivos.iter().any(|(n, _)| n == nome)
+         { remove_se_existir(nome)?; }
+   }
+   // o BD por último, os demais são relativos a ele.
+   for (nome, conteudo) in arquivos.iter().filter(|(n, _)| n != nome_bd)
+      { substitui(nome, &conteudo[..])?; }
+   substitui(nome_bd, &bd[..])?;
+   Ok(anterior)
 }
 
 
@@ -102,33 +228,34 @@ mod tests {
    /* função realiza cinco backups seguidos
     * de intervalos aleatórios. Como não há
     * alteração entre um e outro, então não
-    * realizar todos os backups, apenas o
+    * realiza todos os backups, apenas o
     * primeiro. */
    #[test]
    #[ignore="altera atual banco de backups"]
    fn tenta_realizar_cinco_backups() {
-      // quantia de backups anterior.
+      let primeiro = realiza_backup_bd().unwrap();
+      // quantia de backups depois do primeiro.
       let total: usize = {
-         let path = super::TODOS_BACKUPS;
+         let path = caminho_de(super::TODOS_BACKUPS);
          read_dir(path)
          .unwrap()
          .count()
       };
 
-      for p in 1..=5 { 
-         realiza_backup_bd();
-         println!("{}º backup realizado com sucesso.", p);
+      for p in 2..=5 { 
          sleep(Duration::from_secs(3));
+         assert_eq!(realiza_backup_bd().unwrap(), primeiro);
+         println!("{}º backup reaproveitou o primeiro.", p);
       }
 
       // nova quantia de backups
       let novo_total: usize = {
-         let path = super::TODOS_BACKUPS;
+         let path = caminho_de(super::TODOS_BACKUPS);
          read_dir(path)
          .unwrap()
          .count()
       };
 
-      assert_eq!(total, novo_total-5);
+      assert_eq!(total, novo_total);
    }
 }
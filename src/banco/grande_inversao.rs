@@ -5,21 +5,16 @@
  */
 
 use super::{
-   ultima_insercao_feita as ler_uif, DIR, NOME_BD,
-   ultima_insercao::atualiza_indice_de_insercao
-};
-use std::{
-   io::{self, Read, Write}, collections::{VecDeque}, fs::OpenOptions,
-   path::Path
+   ultima_insercao_feita as ler_uif, carrega_bd, abre_bd, reescreve_bd,
+   ultima_insercao::atualiza_indice_de_insercao, caminho_de
 };
+use std::{io::Write, fs::OpenOptions};
 
 // específico para 4 bytes.
 type Quatro = [u8; 4];
-type Oito = [u8; 8];
-type Fila = VecDeque<Oito>;
 
 // tipo de ordem de armazenamento.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ByteOrdem { LittleEndian, BigEndian }
 
 fn inverte_array_fixa_32bits(bytes: Quatro) -> Quatro {
@@ -29,25 +24,14 @@ fn inverte_array_fixa_32bits(bytes: Quatro) -> Quatro {
    return invertido;
 }
 
-fn inverte_array_fixa_64bits(bytes: Oito) -> Oito {
-   let mut invertido = [0u8; 8];
-   for k in 0..8 
-      { invertido[(8-1)-k] = bytes[k]; }
-   return invertido;
-}
-
 /* Troca o dado armazenado no arquivo para a ordem específicada. Para que 
  * vai, ou baseado no que está, fica à cargo do programador. */
 #[allow(clippy::needless_update, clippy::needless_late_init)]
 pub fn inverte_byte_order_de_todos_dados(ordem: ByteOrdem) {
    // se for a mesma que atual, não faz nada.
-   match atual_byte_order() {
-      ordem => 
-         { panic!("já está nesta ByteOrder({:#?})", ordem); } 
-      #[allow(unreachable_patterns)]
-      _ => 
-         { println!("continuando..."); }
-   };
+   if atual_byte_order() == ordem
+      { panic!("já está nesta ByteOrder({:#?})", ordem); } 
+   println!("continuando...");
    /* arquivo de 'última inserção'. */
    let atual = ler_uif().unwrap();
    let inverte: fn(Quatro) -> Quatro;
@@ -72,50 +56,26 @@ pub fn inverte_byte_order_de_todos_dados(ordem: ByteOrdem) {
    atualiza(novo_valor_le);
 
    // agora faz o mesmo com o arquivo "banco de dados".
-   inverte_byteorder_do_bd();
+   inverte_byteorder_do_bd(ordem);
 
    // o que foi gravado.
    salva_byteorder(ordem);
 }
 
-/* pega todos os bytes do arquivo "banco de dados",
- * e arranja-os de 8 em 8, sendo isto posteriormente
- * retornado por meio de uma fila. */
-fn filtra_em_containers_de_8_bytes() -> io::Result<Fila> {
-   let mut fila = Fila::with_capacity(1_050_000);
-   let caminho = Path::new(DIR).join(NOME_BD);
-   let mut arquivo = {
-      OpenOptions::new()
-      .read(true)
-      .open(caminho)?
-   };
-
-   // pegando 8 bytes de cada vez até o fim do arquivo.
-   'ate_o_fim: loop {
-      let mut buffer = [0u8; 8];
-      match arquivo.read_exact(&mut buffer) {
-         Ok(_) => 
-            { fila.push_back(buffer); }
-         Err(_) => 
-            { break 'ate_o_fim; }
-      };
-   }
-
-   return Ok(fila);
-}
-
-const ORDEM_DOS_BYTES: &str = "data/byte-order.dat";
+const ORDEM_DOS_BYTES: &str = "byte-order.dat";
 /* salva a atual "ordem de byte" imposta aos 
  * valores do banco de dados. Zero representa
  * 'BigEndian', já "duzentos e cinquenta e 
- * cinco", representa o 'LittleEndian'.
+ * cinco", representa o 'LittleEndian'. Quem 
+ * manda agora é o cabeçalho do banco de dados,
+ * este arquivo é só um espelho dele.
  */
 fn salva_byteorder(tipo: ByteOrdem) {
    let mut arquivo = {
       OpenOptions::new()
       .create(true)
       .write(true)
-      .open(ORDEM_DOS_BYTES)
+      .open(caminho_de(ORDEM_DOS_BYTES))
       .unwrap()
    };
    let mut byte: [u8; 1] = [255 / 2];
@@ -129,67 +89,37 @@ fn salva_byteorder(tipo: ByteOrdem) {
 }
 
 /* Inverte, específicamente, o arquivo "banco de dados", o que guarda quase
- * toda informação relevante. */
-#[allow(clippy::needless_late_init)]
-fn inverte_byteorder_do_bd() {
-   /* Arquivo 'banco de dados', com todos dados gerados e coletados. */
-   let mut nova_fila = Fila::with_capacity(1_500_000);
-   let inverte: fn(Oito) -> Oito;
-   inverte = inverte_array_fixa_64bits;
-
-   if let Ok(mut fila) = filtra_em_containers_de_8_bytes() {
-      while !fila.is_empty() {
-         // retira o primeiro elemento, da primeira fila.
-         let atual = fila.pop_front().unwrap();
-         // executa inversão.
-         let bytes = inverte(atual);
-         // adiciona na nova fila..
-         nova_fila.push_back(bytes);
-      }
-   }
-
-   /* Reinsere os containers de bytes, más agora invertidos, no * arquivo 
-    * novamente.. */
-   let caminho = Path::new(DIR).join(NOME_BD);
-   let mut arquivo = {
-      OpenOptions::new()
-      .write(true)
-      .open(caminho)
-      .unwrap()
+ * toda informação relevante. Todos registros são lidos na ordem atual, e 
+ * regravados(de forma atômica) na nova ordem, já com o cabeçalho novo. */
+fn inverte_byteorder_do_bd(ordem: ByteOrdem) {
+   let bd = carrega_bd().unwrap();
+   let buscas: Vec<_> = {
+      (1..=bd.len() as u32)
+      .map(|indice| bd[&indice].clone())
+      .collect()
    };
 
-   while !nova_fila.is_empty() {
-      let container = nova_fila.pop_front().unwrap();
-      let _ = arquivo.write(&container[..]).unwrap();
-   }
+   if let Err(erro) = reescreve_bd(&buscas[..], ordem)
+      { panic!("não foi possível regravar o banco de dados: {}", erro); }
 }
 
 /* a atual ordem que os bytes de todos dados
- * armazenado no programa, estão armazenados.
+ * armazenado no programa, estão armazenados,
+ * como registrado no cabeçalho do BD.
  */
 fn atual_byte_order() -> ByteOrdem {
-   let mut arquivo = {
-      OpenOptions::new()
-      .read(true)
-      .open(ORDEM_DOS_BYTES)
-      .unwrap()
-   };
-   let mut conteudo: [u8; 1] = [255/3];
-   let _ = arquivo.read(&mut conteudo[..]).unwrap();
-
-   if conteudo[0] == 0
-      { ByteOrdem::BigEndian }
-   else if conteudo[0] == 255
-      { ByteOrdem::LittleEndian }
-   else 
-      { panic!("[error]arquivo deve ter sido comrropido"); }
+   match abre_bd() {
+      Ok((_, cabecalho)) => cabecalho.ordem,
+      Err(erro) => 
+         { panic!("[error]arquivo deve ter sido comrropido: {}", erro); }
+   }
 }
 
 #[cfg(test)]
 #[allow(non_snake_case)]
 mod tests {
    use super::*;
-   use crate::banco_de_dados::ultima_insercao_feita;
+   use crate::banco::ultima_insercao_feita;
 
    #[test]
    #[ignore="não é um teste, mas um utilitário escondido"]
@@ -206,23 +136,6 @@ mod tests {
       println!("{:?}", funcao(bytes_em_bigendian));
    }
 
-   #[test]
-   fn inversao8Bytes() {
-      let inverte: fn(Oito) -> Oito;
-      inverte = inverte_array_fixa_64bits;
-      let numero: u64 = 26;
-      let bytes = [0u8, 0, 0, 0, 0, 0, 0, 26];
-      assert_eq!(
-         dbg!(inverte(bytes)), 
-         numero.to_le_bytes()
-      );
-      let bytes = [0u8, 0, 0, 0, 211, 157, 8, 15];
-      assert_eq!(
-         dbg!(inverte(bytes)), 
-         [15, 8, 157, 211, 0, 0, 0, 0u8]
-      );
-   }
-
    #[test]
    fn visualizaAtualByteOrder() 
       { println!("{:#?}", atual_byte_order());}
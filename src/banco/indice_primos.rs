@@ -0,0 +1,443 @@
+/*! Índice dos registros do banco de dados, num arquivo à parte. Cada
+ entrada tem tamanho fixo(48 bytes) e guarda, de um registro, onde ele
+ começa no arquivo do BD, quantos bytes ocupa, quantos primos há antes
+ dele, quantos há nele, e o primeiro e último primo. Assim, consultas por
+ valor ou por ordem são feitas com busca binária no índice, e a leitura
+ de apenas um registro, sem carregar todo o banco de dados.
+
+ O índice é mantido pelo `salva_no_bd`, e quando não bate com o BD(uma
+ migração, uma recuperação, ou ele nunca existiu), é reconstruído.
+*/
+
+use super::formato::{
+   abre_bd, caminho_bd, le_registro, Cabecalho, TAMANHO_CABECALHO
+};
+use super::roda::{modo_roda, contem_na_roda};
+use super::{Busca, caminho_de};
+use std::fs::{File, OpenOptions, rename};
+use std::io::{self, BufReader, BufWriter, Read, Write, Seek, SeekFrom};
+use std::path::PathBuf;
+
+// Nome do arquivo de índice.
+const NOME_INDICE: &str = "indice_primos.dat";
+// Bytes de cada entrada: seis valores de 64-bits.
+const TAMANHO_ENTRADA: u64 = 6 * 8;
+
+/// Uma entrada do índice, referente a um registro do BD.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entrada {
+   // posição, em bytes, do registro no arquivo do BD.
+   pub deslocamento: u64,
+   // total de bytes que o registro ocupa.
+   pub tamanho: u64,
+   // quantia de primos em todos registros anteriores.
+   pub acumulado: u64,
+   // quantia de primos neste registro.
+   pub qtd: u64,
+   pub primeiro: u64,
+   pub ultimo: u64
+}
+
+impl Entrada {
+   /* O 'anterior' é o último valor da entrada que precede esta(zero se não
+    * há nenhuma). Um registro vázio fica com os limites da faixa que
+    * varreu, de logo depois do anterior até o seu último verificado;
+    * assim o índice continua crescente para as buscas binárias. */
+   fn nova(deslocamento: u64, tamanho: u64, acumulado: u64, anterior: u64,
+     dados: &Busca) -> Self
+   {
+      let unv = dados.1;
+      Entrada {
+         deslocamento, tamanho, acumulado,
+         qtd: dados.0.len() as u64,
+         primeiro: {
+            dados.0.first().copied()
+            .unwrap_or(anterior.saturating_add(1).min(unv))
+         },
+         ultimo: dados.0.last().copied().unwrap_or(unv)
+      }
+   }
+
+   fn serializa(&self) -> [u8; TAMANHO_ENTRADA as usize] {
+      let mut bytes = [0u8; TAMANHO_ENTRADA as usize];
+      let valores = [
+         self.deslocamento, self.tamanho, self.acumulado,
+         self.qtd, self.primeiro, self.ultimo
+      ];
+      for (k, v) in valores.iter().enumerate()
+         { bytes[8 * k..8 * (k + 1)].copy_from_slice(&v.to_le_bytes()[..]); }
+      bytes
+   }
+
+   fn deserializa(bytes: &[u8]) -> Self {
+      let v = |k: usize| {
+         let mut a = [0u8; 8];
+         a.copy_from_slice(&bytes[8 * k..8 * (k + 1)]);
+         u64::from_le_bytes(a)
+      };
+      Entrada {
+         deslocamento: v(0), tamanho: v(1), acumulado: v(2),
+         qtd: v(3), primeiro: v(4), ultimo: v(5)
+      }
+   }
+}
+
+fn caminho_indice() -> PathBuf
+   { caminho_de(NOME_INDICE) }
+
+fn total_de_entradas<I: Seek>(indice: &mut I) -> io::Result<u64>
+   { Ok(indice.seek(SeekFrom::End(0))? / TAMANHO_ENTRADA) }
+
+fn le_entrada<I: Read + Seek>(indice: &mut I, i: u64) -> io::Result<Entrada> {
+   let mut bytes = [0u8; TAMANHO_ENTRADA as usize];
+   indice.seek(SeekFrom::Start(i * TAMANHO_ENTRADA))?;
+   indice.read_exact(&mut bytes)?;
+   Ok(Entrada::deserializa(&bytes[..]))
+}
+
+/* Primeira entrada em que o predicado deixa de valer, presupondo que ele
+ * vale para todo um prefixo do índice(o mesmo que 'partition_point'). */
+fn particao<I, P>(indice: &mut I, predicado: P) -> io::Result<u64>
+  where I: Read + Seek, P: Fn(&Entrada) -> bool
+{
+   let (mut baixo, mut alto) = (0, total_de_entradas(indice)?);
+
+   while baixo < alto {
+      let meio = baixo + (alto - baixo) / 2;
+      if predicado(&le_entrada(indice, meio)?)
+         { baixo = meio + 1; }
+      else
+         { alto = meio; }
+   }
+   Ok(baixo)
+}
+
+/* Lê apenas o registro que a entrada aponta. */
+fn le_registro_em<B: Read + Seek>(bd: &mut B, entrada: &Entrada,
+  cabecalho: &Cabecalho) -> io::Result<Busca>
+{
+   bd.seek(SeekFrom::Start(entrada.deslocamento))?;
+   match le_registro(bd, cabecalho)? {
+      Some(busca) => Ok(busca),
+      None => {
+         let msg = "índice aponta para além do fim do BD";
+         Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg))
+      }
+   }
+}
+
+/* Gera todo o índice lendo, sequencialmente, cada registro do BD(este já
+ * posicionado no primeiro registro). */
+fn constroi_indice<B, W>(bd: &mut B, cabecalho: &Cabecalho, saida: &mut W)
+  -> io::Result<u64> where B: Read + Seek, W: Write
+{
+   let mut deslocamento = bd.stream_position()?;
+   let mut acumulado = 0;
+   let mut anterior = 0;
+   let mut total = 0;
+
+   while let Some(busca) = le_registro(bd, cabecalho)? {
+      let posicao = bd.stream_position()?;
+      let tamanho = posicao - deslocamento;
+      let entrada = {
+         Entrada::nova(deslocamento, tamanho, acumulado, anterior, &busca)
+      };
+
+      saida.write_all(&entrada.serializa()[..])?;
+      acumulado += entrada.qtd;
+      anterior = entrada.ultimo;
+      deslocamento = posicao;
+      total += 1;
+   }
+   Ok(total)
+}
+
+/** Reconstrói, do zero, o arquivo de índice. Retorna quantos registros
+ foram indexados. */
+pub fn reconstroi_indice() -> io::Result<u64> {
+   let (bd, cabecalho) = abre_bd()?;
+   let mut bd = BufReader::new(bd);
+   let caminho = caminho_indice();
+   let temporario = caminho.with_extension("dat.novo");
+   let mut saida = BufWriter::new(File::create(&temporario)?);
+
+   bd.seek(SeekFrom::Start(TAMANHO_CABECALHO))?;
+   let total = constroi_indice(&mut bd, &cabecalho, &mut saida)?;
+   saida.flush()?;
+   saida.get_ref().sync_all()?;
+   drop(saida);
+   rename(temporario, caminho)?;
+   Ok(total)
+}
+
+/* O índice só é válido se a sua última entrada termina exatamente onde o
+ * BD termina. */
+fn indice_valido<I: Read + Seek>(indice: &mut I, tamanho_bd: u64)
+  -> io::Result<bool>
+{
+   match total_de_entradas(indice)? {
+      0 => Ok(tamanho_bd <= TAMANHO_CABECALHO),
+      n => {
+         let ultima = le_entrada(indice, n - 1)?;
+         Ok(ultima.deslocamento + ultima.tamanho == tamanho_bd)
+      }
+   }
+}
+
+/* Abre o índice, reconstruindo-o caso não exista ou esteja defasado. */
+fn abre_indice() -> io::Result<File> {
+   let tamanho_bd = caminho_bd().metadata()?.len();
+
+   if let Ok(mut indice) = File::open(caminho_indice()) {
+      if indice_valido(&mut indice, tamanho_bd)?
+         { return Ok(indice); }
+   }
+   reconstroi_indice()?;
+   File::open(caminho_indice())
+}
+
+/** Registra no índice o registro que acabou de ser anexado ao BD. Se o
+ índice não está em dia com o BD, ele é reconstruído inteiro. */
+pub fn anexa_ao_indice(deslocamento: u64, tamanho: u64, dados: &Busca)
+  -> io::Result<()>
+{
+   let caminho = caminho_indice();
+   let mut indice = {
+      OpenOptions::new()
+      .read(true).write(true).create(true)
+      .open(&caminho)?
+   };
+   // o acumulado de primos, e o último valor, até esta entrada.
+   let precedente = match total_de_entradas(&mut indice)? {
+      0 if deslocamento == TAMANHO_CABECALHO => Some((0, 0)),
+      0 => None,
+      n => {
+         let ultima = le_entrada(&mut indice, n - 1)?;
+         if ultima.deslocamento + ultima.tamanho == deslocamento
+            { Some((ultima.acumulado + ultima.qtd, ultima.ultimo)) }
+         else
+            { None }
+      }
+   };
+
+   match precedente {
+      Some((acumulado, anterior)) => {
+         let entrada = Entrada::nova(
+            deslocamento, tamanho, acumulado, 
+            anterior, dados
+         );
+         indice.seek(SeekFrom::End(0))?;
+         indice.write_all(&entrada.serializa()[..])
+      } None => {
+         drop(indice);
+         reconstroi_indice().map(|_| ())
+      }
+   }
+}
+
+fn contem_em<B, I>(bd: &mut B, indice: &mut I, cabecalho: &Cabecalho,
+  n: u64) -> io::Result<bool> where B: Read + Seek, I: Read + Seek
+{
+   // primeiro registro que não termina antes de 'n'.
+   let i = particao(indice, |e| e.ultimo < n)?;
+
+   if i == total_de_entradas(indice)?
+      { return Ok(false); }
+   let entrada = le_entrada(indice, i)?;
+   if entrada.primeiro > n
+      { return Ok(false); }
+   let busca = le_registro_em(bd, &entrada, cabecalho)?;
+   Ok(busca.0.binary_search(&n).is_ok())
+}
+
+fn n_esimo_em<B, I>(bd: &mut B, indice: &mut I, cabecalho: &Cabecalho,
+  k: u64) -> io::Result<Option<u64>> where B: Read + Seek, I: Read + Seek
+{
+   if k == 0
+      { return Ok(None); }
+   // registro em que os primos acumulados até ele chegam em 'k'.
+   let i = particao(indice, |e| e.acumulado + e.qtd < k)?;
+
+   if i == total_de_entradas(indice)?
+      { return Ok(None); }
+   let entrada = le_entrada(indice, i)?;
+   let busca = le_registro_em(bd, &entrada, cabecalho)?;
+   Ok(busca.0.get((k - entrada.acumulado - 1) as usize).copied())
+}
+
+fn primos_entre_em<B, I>(bd: &mut B, indice: &mut I, cabecalho: &Cabecalho,
+  a: u64, b: u64) -> io::Result<Vec<u64>>
+  where B: Read + Seek, I: Read + Seek
+{
+   let mut primos = Vec::<u64>::new();
+   let total = total_de_entradas(indice)?;
+   let mut i = particao(indice, |e| e.ultimo < a)?;
+
+   while i < total {
+      let entrada = le_entrada(indice, i)?;
+      if entrada.primeiro > b
+         { break; }
+      let busca = le_registro_em(bd, &entrada, cabecalho)?;
+      primos.extend(busca.0.into_iter().filter(|p| *p >= a && *p <= b));
+      i += 1;
+   }
+   Ok(primos)
+}
+
+/** Diz se o número dado está entre os primos do banco de dados. */
+pub fn contem(n: u64) -> io::Result<bool> {
+   // no modo roda, não há índice: é só olhar o bit do número.
+   if modo_roda()
+      { return contem_na_roda(n); }
+   let (mut bd, cabecalho) = abre_bd()?;
+   let mut indice = abre_indice()?;
+   contem_em(&mut bd, &mut indice, &cabecalho, n)
+}
+
+/** O k-ésimo primo guardado(começando do primeiro), se houver tantos. */
+pub fn n_esimo(k: u64) -> io::Result<Option<u64>> {
+   let (mut bd, cabecalho) = abre_bd()?;
+   let mut indice = abre_indice()?;
+   n_esimo_em(&mut bd, &mut indice, &cabecalho, k)
+}
+
+/** Todos primos guardados no intervalo fechado [a, b]. */
+pub fn primos_entre(a: u64, b: u64) -> io::Result<Vec<u64>> {
+   let (mut bd, cabecalho) = abre_bd()?;
+   let mut indice = abre_indice()?;
+   primos_entre_em(&mut bd, &mut indice, &cabecalho, a, b)
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use super::super::ByteOrdem;
+   use super::super::formato::serializa_busca;
+   use crate::motor::crivo_segmentado;
+   use std::io::Cursor;
+
+   type Memoria = Cursor<Vec<u8>>;
+
+   /* BD em memória com os registros dados, e já com o seu índice. */
+   fn bd_com(buscas: Vec<Busca>) -> (Memoria, Memoria) {
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+      let mut bytes = cabecalho.serializa().to_vec();
+      let total = buscas.len() as u64;
+
+      for busca in buscas
+         { bytes.extend(serializa_busca(&busca, &cabecalho)); }
+      let mut bd = Cursor::new(bytes);
+      let mut indice = Cursor::new(Vec::new());
+      bd.seek(SeekFrom::Start(TAMANHO_CABECALHO)).unwrap();
+      assert_eq!(constroi_indice(&mut bd, &cabecalho, &mut indice).unwrap(), total);
+      (bd, indice)
+   }
+
+   /* BD em memória com os primos até 10 mil, em registros de 100 números
+    * cada, e já com o seu índice. */
+   fn bd_em_memoria() -> (Memoria, Memoria, Vec<u64>) {
+      let mut buscas = Vec::<Busca>::new();
+      let mut todos = Vec::<u64>::new();
+
+      for inicio in (0..10_000).step_by(100) {
+         let primos = crivo_segmentado(inicio, inicio + 99);
+         todos.extend(&primos[..]);
+         buscas.push((primos, inicio + 99, 1, 1));
+      }
+      let (bd, indice) = bd_com(buscas);
+      (bd, indice, todos)
+   }
+
+   #[test]
+   fn EntradaIdaEVolta() {
+      let entrada = Entrada::nova(16, 300, 5, 6, &(vec![7, 11, 13], 14, 0, 1));
+      assert_eq!(Entrada::deserializa(&entrada.serializa()[..]), entrada);
+      // o vázio fica com a faixa que varreu.
+      let vazio = Entrada::nova(16, 300, 5, 23, &(vec![], 28, 0, 1));
+      assert_eq!((vazio.primeiro, vazio.ultimo), (24, 28));
+   }
+
+   #[test]
+   fn RegistroVazioNoMeio() {
+      let todos = crivo_segmentado(0, 100);
+      let (mut bd, mut indice) = bd_com(vec![
+         (crivo_segmentado(0, 24), 24, 1, 1),
+         // entre 23 e 29 não há primos.
+         (vec![], 28, 1, 1),
+         (crivo_segmentado(29, 60), 60, 1, 1),
+         (vec![], 60, 1, 1),
+         (crivo_segmentado(61, 100), 100, 1, 1)
+      ]);
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+
+      for n in 0..110 {
+         let esperado = todos.binary_search(&n).is_ok();
+         assert_eq!(contem_em(&mut bd, &mut indice, &cabecalho, n).unwrap(), esperado);
+      }
+      for (a, b) in [(0, 5), (20, 40), (24, 28), (25, 100), (59, 62)] {
+         let esperado: Vec<u64> = {
+            todos.iter().copied()
+            .filter(|p| *p >= a && *p <= b)
+            .collect()
+         };
+         let obtido = primos_entre_em(&mut bd, &mut indice, &cabecalho, a, b);
+         assert_eq!(obtido.unwrap(), esperado);
+      }
+      for (k, p) in todos.iter().enumerate() {
+         let k = k as u64 + 1;
+         assert_eq!(n_esimo_em(&mut bd, &mut indice, &cabecalho, k).unwrap(), Some(*p));
+      }
+   }
+
+   #[test]
+   fn IndiceConfereComOBD() {
+      let (bd, mut indice, _) = bd_em_memoria();
+      let tamanho = bd.get_ref().len() as u64;
+
+      assert!(indice_valido(&mut indice, tamanho).unwrap());
+      assert!(!indice_valido(&mut indice, tamanho + 100).unwrap());
+   }
+
+   #[test]
+   fn ConsultasPorValor() {
+      let (mut bd, mut indice, todos) = bd_em_memoria();
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+
+      for n in 0..10_050 {
+         let esperado = todos.binary_search(&n).is_ok();
+         assert_eq!(contem_em(&mut bd, &mut indice, &cabecalho, n).unwrap(), esperado);
+      }
+   }
+
+   #[test]
+   fn ConsultasPorOrdem() {
+      let (mut bd, mut indice, todos) = bd_em_memoria();
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+
+      assert_eq!(n_esimo_em(&mut bd, &mut indice, &cabecalho, 0).unwrap(), None);
+      for (k, p) in todos.iter().enumerate() {
+         let k = k as u64 + 1;
+         assert_eq!(n_esimo_em(&mut bd, &mut indice, &cabecalho, k).unwrap(), Some(*p));
+      }
+      let alem = todos.len() as u64 + 1;
+      assert_eq!(n_esimo_em(&mut bd, &mut indice, &cabecalho, alem).unwrap(), None);
+   }
+
+   #[test]
+   fn ConsultasPorFaixa() {
+      let (mut bd, mut indice, todos) = bd_em_memoria();
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+
+      for (a, b) in [(0, 10), (95, 105), (1_000, 3_333), (9_990, 20_000)] {
+         let esperado: Vec<u64> = {
+            todos.iter().copied()
+            .filter(|p| *p >= a && *p <= b)
+            .collect()
+         };
+         let obtido = primos_entre_em(&mut bd, &mut indice, &cabecalho, a, b);
+         assert_eq!(obtido.unwrap(), esperado);
+      }
+   }
+}
@@ -0,0 +1,137 @@
+/*! Manifesto dos backups: um pequeno arquivo de texto em 'backups/', com
+ uma linha para cada pacote gravado, a impressão digital(FNV-1a de 64 bits)
+ de tudo que ele embrulha, e o seu nome:
+
+    <impressão, em hexadecimal> <nome do pacote>
+
+ Com ele, um backup idêntico a um que já existe não é gravado de novo, e a
+ restauração confere se o pacote é o mesmo que foi gravado. Linhas de
+ pacotes que já não existem(podados, ou apagados à mão) são esquecidas na
+ próxima escrita.
+*/
+
+use super::{caminho_de, pacote::Embrulho};
+use std::fs::{OpenOptions, read_to_string, rename};
+use std::io::{self, Write, ErrorKind};
+use std::path::PathBuf;
+
+const NOME_MANIFESTO: &str = "backups/manifesto.txt";
+const FNV_BASE: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIMO: u64 = 0x0000_0100_0000_01b3;
+
+/** Impressão digital dos arquivos embrulhados: conta o nome, o tamanho e
+ o conteúdo de cada um, na ordem dada. */
+pub fn impressao_digital(arquivos: &[Embrulho]) -> u64 {
+   let mut hash = FNV_BASE;
+   let mut mistura = |bytes: &[u8]| {
+      for &b in bytes
+         { hash = (hash ^ b as u64).wrapping_mul(FNV_PRIMO); }
+   };
+
+   for (nome, conteudo) in arquivos {
+      mistura(nome.as_bytes());
+      mistura(&[0]);
+      mistura(&(conteudo.len() as u64).to_le_bytes());
+      mistura(&conteudo[..]);
+   }
+   hash
+}
+
+fn caminho_do_pacote(nome: &str) -> PathBuf
+   { caminho_de("backups").join(nome) }
+
+/* Lê uma linha do manifesto, linhas estragadas são só ignoradas. */
+fn interpreta_linha(linha: &str) -> Option<(u64, String)> {
+   let (impressao, nome) = linha.trim().split_once(' ')?;
+   let impressao = u64::from_str_radix(impressao, 16).ok()?;
+   let nome = nome.trim();
+
+   if nome.is_empty() || nome.contains('/')
+      { return None; }
+   Some((impressao, nome.to_string()))
+}
+
+/* Todas entradas do manifesto cujo pacote ainda existe. */
+fn entradas() -> Vec<(u64, String)> {
+   let conteudo = read_to_string(caminho_de(NOME_MANIFESTO));
+
+   conteudo.unwrap_or_default()
+   .lines()
+   .filter_map(interpreta_linha)
+   .filter(|(_, nome)| caminho_do_pacote(nome).is_file())
+   .collect()
+}
+
+/** Acrescenta o pacote dado ao manifesto. O manifesto é reescrito por
+ inteiro(e de forma atômica), já sem os pacotes que não existem mais. */
+pub fn registra_no_manifesto(impressao: u64, nome: &str) -> io::Result<()> {
+   let mut lista = entradas();
+   lista.retain(|(_, n)| n != nome);
+   lista.push((impressao, nome.to_string()));
+
+   let destino = caminho_de(NOME_MANIFESTO);
+   let temporario = destino.with_extension("novo");
+   let mut arquivo = {
+      OpenOptions::new()
+      .create(true).write(true).truncate(true)
+      .open(&temporario)?
+   };
+   for (impressao, nome) in lista.iter()
+      { writeln!(arquivo, "{:016x} {}", impressao, nome)?; }
+   arquivo.sync_all()?;
+   drop(arquivo);
+   rename(&temporario, &destino)
+}
+
+/** O pacote mais recente registrado com a impressão dada, se ainda existir. */
+pub fn pacote_com_impressao(impressao: u64) -> Option<PathBuf> {
+   entradas().into_iter().rev()
+   .find(|&(i, _)| i == impressao)
+   .map(|(_, nome)| caminho_do_pacote(&nome))
+}
+
+/** A impressão registrada para o pacote de nome dado. Não estar no
+ manifesto não é erro, pacotes antigos não estão nele. */
+pub fn impressao_registrada(nome: &str) -> io::Result<Option<u64>> {
+   match read_to_string(caminho_de(NOME_MANIFESTO)) {
+      Ok(conteudo) => Ok(
+         conteudo.lines().rev()
+         .filter_map(interpreta_linha)
+         .find(|(_, n)| n == nome)
+         .map(|(i, _)| i)
+      ),
+      Err(ref erro) if erro.kind() == ErrorKind::NotFound => Ok(None),
+      Err(erro) => Err(erro)
+   }
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn ImpressaoDigitalELinhas() {
+      let a = vec![(String::from("a.dat"), vec![1, 2, 3])];
+      let b = vec![(String::from("a.dat"), vec![1, 2, 4])];
+      let c = vec![(String::from("b.dat"), vec![1, 2, 3])];
+      // juntar o nome no conteúdo não pode dar na mesma impressão.
+      let d = vec![(String::from("a.da"), b"t\0\x03".to_vec())];
+
+      assert_eq!(impressao_digital(&a), impressao_digital(&a.clone()));
+      assert_ne!(impressao_digital(&a), impressao_digital(&b));
+      assert_ne!(impressao_digital(&a), impressao_digital(&c));
+      assert_ne!(impressao_digital(&a), impressao_digital(&d));
+      assert_eq!(impressao_digital(&[]), FNV_BASE);
+
+      let linha = format!("{:016x} {}", 0xdead_beef_u64, "backup_bd_1.pacote");
+      assert_eq!(
+         interpreta_linha(linha.as_str()),
+         Some((0xdead_beef, String::from("backup_bd_1.pacote")))
+      );
+      assert_eq!(interpreta_linha("xyz backup_bd_1.pacote"), None);
+      assert_eq!(interpreta_linha("00ff"), None);
+      assert_eq!(interpreta_linha("00ff ../../etc/passwd"), None);
+   }
+}
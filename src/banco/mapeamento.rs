@@ -0,0 +1,276 @@
+/*! Leitura do banco de dados mapeado em memória(mmap). O arquivo inteiro
+ fica visível como uma fatia de bytes, sem ser copiado, e os registros e
+ os seus primos são percorridos sob demanda, direto dela: é o sistema
+ operacional que traz do disco só as páginas que forem lidas.
+
+ O BD nunca é reescrito no lugar(ver `reescreve_bd`), só anexado; então o
+ que estiver mapeado segue válido até o fim da leitura.
+*/
+
+use super::Busca;
+use super::codec::{decodifica, PrimosCodificados};
+use super::formato::{
+   abre_bd, crc32, crc_de_bytes, de_bytes, Cabecalho, TAMANHO_CABECALHO
+};
+use libc::{c_void, mmap, munmap, MAP_FAILED, MAP_PRIVATE, PROT_READ};
+use std::fs::File;
+use std::io::{self, Error, ErrorKind};
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::ptr::null_mut;
+use std::slice::from_raw_parts;
+
+/* Um arquivo mapeado, só para leitura; é desmapeado ao sair de escopo. */
+struct Mapa { inicio: *mut c_void, tamanho: usize }
+
+impl Mapa {
+   fn de(arquivo: &File) -> io::Result<Self> {
+      let tamanho = arquivo.metadata()?.len() as usize;
+
+      // não dá para mapear zero bytes.
+      if tamanho == 0
+         { return Ok(Mapa { inicio: null_mut(), tamanho }); }
+      let inicio = unsafe {
+         mmap(
+            null_mut(), tamanho, PROT_READ, MAP_PRIVATE,
+            arquivo.as_raw_fd(), 0
+         )
+      };
+      if inicio == MAP_FAILED
+         { return Err(Error::last_os_error()); }
+      Ok(Mapa { inicio, tamanho })
+   }
+}
+
+impl Deref for Mapa {
+   type Target = [u8];
+
+   fn deref(&self) -> &[u8] {
+      if self.tamanho == 0
+         { return &[]; }
+      unsafe { from_raw_parts(self.inicio as *const u8, self.tamanho) }
+   }
+}
+
+impl Drop for Mapa {
+   fn drop(&mut self) {
+      if self.tamanho > 0
+         { unsafe { munmap(self.inicio, self.tamanho); } }
+   }
+}
+
+/** Um registro do BD, ainda nos bytes do mapa(já conferido pelo CRC). */
+pub struct Registro<'a> { conteudo: &'a [u8], cabecalho: Cabecalho }
+
+impl<'a> Registro<'a> {
+   /** Os primos do registro, decodificados um a um. */
+   pub fn primos(&self) -> io::Result<PrimosCodificados<'a>> {
+      let Cabecalho { versao, ordem, .. } = self.cabecalho;
+      PrimosCodificados::novos(self.conteudo, versao, ordem)
+   }
+
+   /** Último número verificado, tempo e buscas do registro. */
+   pub fn metadados(&self) -> io::Result<(u64, u64, u64)>
+      { self.primos()?.metadados() }
+
+   /** O registro inteiro, copiado para fora do mapa. */
+   pub fn busca(&self) -> io::Result<Busca> {
+      let Cabecalho { versao, ordem, .. } = self.cabecalho;
+      decodifica(self.conteudo, versao, ordem)
+   }
+}
+
+/** O banco de dados(no modo lista) mapeado em memória. */
+pub struct BDMapeado { mapa: Mapa, cabecalho: Cabecalho }
+
+impl BDMapeado {
+   /** Mapeia o BD, depois de aberto(e, se for preciso, migrado ou
+    recuperado) por `abre_bd`. */
+   pub fn abre() -> io::Result<Self> {
+      let (arquivo, cabecalho) = abre_bd()?;
+      Ok(BDMapeado { mapa: Mapa::de(&arquivo)?, cabecalho })
+   }
+
+   /** O registro que começa na posição dada do arquivo, e a posição do
+    seguinte; nada no fim do arquivo. */
+   pub fn registro_em(&self, posicao: usize)
+     -> io::Result<Option<(Registro<'_>, usize)>>
+   {
+      let ordem = self.cabecalho.ordem;
+      let incompleto = || {
+         Error::new(ErrorKind::UnexpectedEof, "registro incompleto")
+      };
+      let bytes = match self.mapa.get(posicao..) {
+         Some(bytes) if !bytes.is_empty() => bytes,
+         _ => return Ok(None)
+      };
+      let mut tamanho = [0u8; 8];
+      tamanho.copy_from_slice(bytes.get(0..8).ok_or_else(incompleto)?);
+      let tamanho = de_bytes(tamanho, ordem);
+
+      // conteúdo, mais os 4 bytes do CRC.
+      let fim = {
+         tamanho.checked_add(12)
+         .filter(|&fim| fim <= bytes.len() as u64)
+         .ok_or_else(incompleto)? as usize
+      };
+      let (conteudo, crc) = bytes[8..fim].split_at(fim - 12);
+      let mut array = [0u8; 4];
+      array.copy_from_slice(crc);
+
+      if crc_de_bytes(array, ordem) != crc32(conteudo) {
+         let msg = "CRC-32 do registro não confere, dados corrompidos";
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+      let registro = Registro { conteudo, cabecalho: self.cabecalho };
+      Ok(Some((registro, posicao + fim)))
+   }
+
+   /** Todos registros do BD, na ordem. */
+   pub fn registros(&self) -> Registros<'_>
+      { Registros { bd: self, posicao: Some(TAMANHO_CABECALHO as usize) } }
+
+   /** Todos primos do BD, na ordem dos registros. */
+   pub fn primos(&self) -> impl Iterator<Item = io::Result<u64>> + '_ {
+      self.registros().flat_map(|registro| {
+         let primos = registro.and_then(|r| r.primos());
+         // um erro é repassado como o único item do registro.
+         let (primos, erro) = match primos {
+            Ok(primos) => (Some(primos), None),
+            Err(erro) => (None, Some(Err(erro)))
+         };
+         primos.into_iter().flatten().chain(erro)
+      })
+   }
+
+   /** Todas buscas do BD, na ordem, levando o mapa junto. */
+   pub fn em_buscas(self) -> BuscasMapeadas {
+      let posicao = Some(TAMANHO_CABECALHO as usize);
+      BuscasMapeadas { bd: self, posicao }
+   }
+}
+
+/** As buscas do BD mapeado, cada uma copiada só quando chega a sua vez;
+ é dono do mapa, então pode sair da função que o abriu. Para depois de um
+ erro. */
+pub struct BuscasMapeadas { bd: BDMapeado, posicao: Option<usize> }
+
+impl Iterator for BuscasMapeadas {
+   type Item = io::Result<Busca>;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      let lido = {
+         self.bd.registro_em(self.posicao?)
+         .and_then(|registro| match registro {
+            Some((registro, seguinte)) =>
+               { Ok(Some((registro.busca()?, seguinte))) }
+            None => Ok(None)
+         })
+      };
+      match lido {
+         Ok(Some((busca, seguinte))) => {
+            self.posicao = Some(seguinte);
+            Some(Ok(busca))
+         } Ok(None) => {
+            self.posicao = None;
+            None
+         } Err(erro) => {
+            self.posicao = None;
+            Some(Err(erro))
+         }
+      }
+   }
+}
+
+/** Iterador dos registros do BD mapeado; para depois de um erro. */
+pub struct Registros<'a> { bd: &'a BDMapeado, posicao: Option<usize> }
+
+impl<'a> Iterator for Registros<'a> {
+   type Item = io::Result<Registro<'a>>;
+
+   fn next(&mut self) -> Option<Self::Item> {
+      match self.bd.registro_em(self.posicao?) {
+         Ok(Some((registro, seguinte))) => {
+            self.posicao = Some(seguinte);
+            Some(Ok(registro))
+         } Ok(None) => {
+            self.posicao = None;
+            None
+         } Err(erro) => {
+            self.posicao = None;
+            Some(Err(erro))
+         }
+      }
+   }
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use super::super::ByteOrdem;
+   use super::super::formato::serializa_busca;
+   use std::env::temp_dir;
+   use std::fs::{write, remove_file};
+
+   /* BD num arquivo temporário, já mapeado. */
+   fn mapeia(nome: &str, bytes: &[u8], cabecalho: Cabecalho) -> BDMapeado {
+      let caminho = temp_dir().join(nome);
+      write(&caminho, bytes).unwrap();
+      let mapa = Mapa::de(&File::open(&caminho).unwrap()).unwrap();
+      remove_file(caminho).unwrap();
+      BDMapeado { mapa, cabecalho }
+   }
+
+   #[test]
+   fn PercorreRegistrosEPrimos() {
+      let buscas: Vec<Busca> = vec![
+         (vec![2, 3, 5, 7], 10, 1, 1),
+         (vec![], 12, 2, 2),
+         (vec![11, 13, 17, 19, 23], 24, 3, 3)
+      ];
+      for ordem in [ByteOrdem::LittleEndian, ByteOrdem::BigEndian] {
+         let cabecalho = Cabecalho::novo(ordem);
+         let mut bytes = cabecalho.serializa().to_vec();
+         for busca in buscas.iter()
+            { bytes.extend(serializa_busca(busca, &cabecalho)); }
+         let bd = mapeia("mapeado.dat", &bytes[..], cabecalho);
+
+         let lidas: Vec<Busca> = {
+            bd.registros()
+            .map(|r| r.unwrap().busca().unwrap())
+            .collect()
+         };
+         assert_eq!(lidas, buscas);
+         let primos: Vec<u64> = bd.primos().map(Result::unwrap).collect();
+         assert_eq!(primos, vec![2, 3, 5, 7, 11, 13, 17, 19, 23]);
+         let ultimo = bd.registros().last().unwrap().unwrap();
+         assert_eq!(ultimo.metadados().unwrap(), (24, 3, 3));
+         // e sem estar preso ao mapa.
+         let lidas: Vec<Busca> = bd.em_buscas().map(Result::unwrap).collect();
+         assert_eq!(lidas, buscas);
+      }
+   }
+
+   #[test]
+   fn RegistroCortadoTerminaComErro() {
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+      let mut bytes = cabecalho.serializa().to_vec();
+      bytes.extend(serializa_busca(&(vec![2, 3], 4, 1, 1), &cabecalho));
+      let segundo = serializa_busca(&(vec![5, 7], 8, 1, 1), &cabecalho);
+      bytes.extend(&segundo[..segundo.len() - 3]);
+      let bd = mapeia("mapeado-cortado.dat", &bytes[..], cabecalho);
+
+      let registros: Vec<_> = bd.registros().collect();
+      assert_eq!(registros.len(), 2);
+      assert!(registros[0].is_ok());
+      assert!(registros[1].is_err());
+      let primos: Vec<_> = bd.primos().collect();
+      assert_eq!(primos.len(), 3);
+      assert!(primos[2].is_err());
+      // vazio, nem o cabeçalho.
+      let vazio = mapeia("mapeado-vazio.dat", &[], cabecalho);
+      assert_eq!(vazio.registros().count(), 0);
+   }
+}
@@ -1,102 +1,242 @@
-
-/* faz a organização do BD, que geralmente é 
+/* faz a organização do BD, que geralmente é
  * gravado com o préfixo "backup", mais o tempo
  * de sistema que está sendo criado na frente.
- * Vamos continuar com o préfixo, porém mudar 
- * o selo do sistema, e colocar um número 
+ * Vamos continuar com o préfixo, porém mudar
+ * o selo do sistema, e colocar um número
  * romano no local. */
 
-use std::fs::{DirEntry, ReadDir};
+use std::fs::{DirEntry, ReadDir, read_dir, remove_file};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use utilitarios::romanos::decimal_para_romano;
+use super::caminho_de;
 
 // facilita codificação:
 type Entradas = Vec<DirEntry>;
+// extensão dos pacotes de backup.
+const EXTENSAO: &str = "pacote";
+/* extensão dos backups de antes dos pacotes('backup_bd_<millis>.zip');
+ * não são mais gerados, mas ainda ocupam espaço, e são podados também. */
+const EXTENSAO_LEGADA: &str = "zip";
 
 /* extrai a duração da "entrada do diretório"
- * dada, referente a sua data de criação. */
+ * dada, referente a sua última modificação(nem
+ * todo sistema de arquivos guarda a criação). */
 fn decorrido(entrada: &DirEntry) -> Duration {
-   let mt = entrada.metadata().unwrap();
-   return {
-      mt.created().unwrap()
-      .elapsed()
-      .unwrap()
-   };
+   entrada.metadata()
+   .and_then(|mt| mt.modified())
+   .ok()
+   .and_then(|t| t.elapsed().ok())
+   .unwrap_or_default()
 }
 
 /* tamanho da entrada, se for um arquivo. */
-fn size(e: &DirEntry) -> u64 
-   { e.metadata().unwrap().len() }
+fn size(e: &DirEntry) -> u64
+   { e.metadata().map_or(0, |mt| mt.len()) }
 
 
+#[allow(dead_code)]
 enum Ordenacao { Tamanho, Tempo }
-/* ordena lista de acordo com a data 
+/* ordena lista de acordo com a data
  * de criação. Em ordem crescente, ou seja,
- * os mais recentes ficam na esquerda da 
+ * os mais recentes ficam na esquerda da
  * array. */
 fn ordena(lista: ReadDir, tipo: Ordenacao) -> Entradas {
-   let mut array = Entradas::new();
-
-   // inserindo já ordenando(insert-sort).
-   for entrada in lista {
-      let e = entrada.unwrap();
-      /* se não há elementos, apenas adiciona
-       * e reseta o 'loop'. */
-      if array.len() == 0
-         { array.push(e); continue; }
-      let t1 = decorrido(&e);
-      let s1 = size(&e);
-      let mut indice = 0;
-      for (i, x) in array.iter().enumerate() {
-         let t2 = decorrido(&x);
-         let s2 = size(&x);
-         // atualiza o índice atual.
-         indice = i;
-         match tipo {
-            Ordenacao::Tempo => {
-               if t1 < t2
-                  { break; }
-            } Ordenacao::Tamanho => {
-               if s1 < s2
-                  { break; }
-            }
-         };
-      }
-      // adiciona no índice parado.
-      array.insert(indice, e);
-   }
+   let mut array: Entradas = lista.filter_map(Result::ok).collect();
+
+   match tipo {
+      Ordenacao::Tempo =>
+         { array.sort_by_key(decorrido); }
+      Ordenacao::Tamanho =>
+         { array.sort_by_key(size); }
+   };
    return array;
 }
 
 // extrai nome da entrada que foi referênciada.
-fn nome(entrada: &DirEntry) -> String { 
+fn nome(entrada: &DirEntry) -> String {
    entrada.path()
    .as_path()
    .file_name().unwrap()
-   .to_str().unwrap()
-   .to_string() 
+   .to_string_lossy()
+   .to_string()
 }
 
-/* renomea 'Entradas' dadas para um novo
- * formato de identificação, que leva em
- * conta a contagem romana. Aceita basicamente
- * a ordem na 'array', então, não segue 
+/* numera 'Entradas' dadas num novo formato
+ * de identificação, que leva em conta a
+ * contagem romana. Aceita basicamente a
+ * ordem na 'array', então, não segue
  * critério de "tempo de criação" ou "tamanho"
- * tal ordenação, apenas segue a ordem da array. */
-pub fn renomea(mut lista_ordenada: Entradas) {
+ * tal ordenação, apenas segue a ordem da array:
+ * o primeiro recebe o maior número. Os arquivos
+ * não são renomeados, o número é só um rótulo. */
+fn numera(mut lista_ordenada: Entradas) -> Vec<(String, DirEntry)> {
    let mut tamanho = lista_ordenada.len();
+   let mut numerados = Vec::with_capacity(tamanho);
+
    for entrada in lista_ordenada.drain(0..) {
       let t = tamanho as u16;
-      let numero_romano = decimal_para_romano(t);
-      let antigo = nome(&entrada);
-      let novo_nome = format!("backup.{}.zip", numero_romano);
-      println!(
-         "{} >>> {:#?}", 
-         antigo, 
-         novo_nome.to_lowercase()
-      );
+      let numero_romano = decimal_para_romano(t).to_lowercase();
+      numerados.push((numero_romano, entrada));
       tamanho -= 1;
    }
+   numerados
+}
+
+/// Um backup listado: o seu rótulo romano, nome, caminho, tamanho e idade.
+pub struct Backup {
+   pub rotulo: String,
+   pub nome: String,
+   pub caminho: PathBuf,
+   pub tamanho: u64,
+   pub idade: Duration,
+   // um '.zip' antigo, que não tem como ser restaurado.
+   pub legado: bool
+}
+
+/* Se a entrada tem a extensão dada. */
+fn tem_extensao(entrada: &DirEntry, extensao: &str) -> bool
+   { entrada.path().extension().is_some_and(|ext| ext == extensao) }
+
+/** Lista todos os backups, do mais recente ao mais antigo, os pacotes e
+ também os '.zip' antigos. O mais antigo é o "i", e o mais recente tem o
+ maior número romano. */
+pub fn lista_backups() -> io::Result<Vec<Backup>> 
+   { lista_backups_em(&caminho_de("backups")) }
+
+fn lista_backups_em(diretorio: &Path) -> io::Result<Vec<Backup>> {
+   let entradas = match read_dir(diretorio) {
+      Ok(entradas) => entradas,
+      Err(ref erro) if erro.kind() == io::ErrorKind::NotFound =>
+         { return Ok(Vec::new()); }
+      Err(erro) => return Err(erro)
+   };
+   let pacotes: Entradas = {
+      ordena(entradas, Ordenacao::Tempo)
+      .into_iter()
+      .filter(|e| {
+         tem_extensao(e, EXTENSAO) || tem_extensao(e, EXTENSAO_LEGADA)
+      }).collect()
+   };
+
+   Ok(numera(pacotes).into_iter().map(|(rotulo, e)| {
+      Backup { 
+         rotulo, nome: nome(&e), caminho: e.path(), 
+         tamanho: size(&e), idade: decorrido(&e),
+         legado: tem_extensao(&e, EXTENSAO_LEGADA)
+      }
+   }).collect())
+}
+
+/** Acha o backup pelo rótulo romano("iii"), pelo nome do arquivo, ou por
+ um caminho qualquer. */
+pub fn escolhe_backup(escolha: &str) -> io::Result<Option<PathBuf>> {
+   let escolha = escolha.trim();
+   let caminho = PathBuf::from(escolha);
+
+   if caminho.is_file()
+      { return Ok(Some(caminho)); }
+   Ok(lista_backups()?.into_iter().find(|b| {
+      b.rotulo.eq_ignore_ascii_case(escolha) || b.nome == escolha
+   }).map(|b| b.caminho))
+}
+
+const DIA: u64 = 24 * 60 * 60;
+
+/** Quantos backups são mantidos: os últimos N, mais um por dia, por 
+ semana e por mês(contados da idade de cada um, não do calendário), e 
+ nunca mais que o tamanho máximo somado. O mais recente é sempre mantido. */
+pub struct Retencao {
+   pub ultimos: usize,
+   pub diarios: usize,
+   pub semanais: usize,
+   pub mensais: usize,
+   pub tamanho_maximo: u64
+}
+
+/// A política usada depois de cada backup.
+pub const RETENCAO_PADRAO: Retencao = Retencao {
+   ultimos: 5, diarios: 7, semanais: 4, mensais: 6,
+   tamanho_maximo: 1024 * 1024 * 1024
+};
+
+/* Marca, na lista(do mais recente ao mais antigo) de idades em segundos e
+ * tamanhos, quais backups a política mantém. */
+fn mantidos(lista: &[(u64, u64)], retencao: &Retencao) -> Vec<bool> {
+   let mut manter: Vec<bool> = {
+      (0..lista.len())
+      .map(|i| i < retencao.ultimos.max(1))
+      .collect()
+   };
+
+   // o mais recente de cada período, até a quantia de períodos dada.
+   for &(periodo, qtd) in [(DIA, retencao.diarios), 
+     (7 * DIA, retencao.semanais), (30 * DIA, retencao.mensais)].iter()
+   {
+      let mut vistos: Vec<u64> = Vec::with_capacity(qtd);
+      for (i, &(idade, _)) in lista.iter().enumerate() {
+         let balde = idade / periodo;
+         if vistos.len() == qtd
+            { break; }
+         if !vistos.contains(&balde) 
+            { vistos.push(balde); manter[i] = true; }
+      }
+   }
+
+   // passando do tamanho máximo, os mais antigos vão embora.
+   let mut acumulado = 0u64;
+   for (i, &(_, tamanho)) in lista.iter().enumerate() {
+      if !manter[i]
+         { continue; }
+      acumulado = acumulado.saturating_add(tamanho);
+      if i > 0 && acumulado > retencao.tamanho_maximo
+         { manter[i] = false; }
+   }
+   manter
+}
+
+/** Lista os backups(do mais recente ao mais antigo), cada um marcado com
+ se a política o mantém. */
+pub fn classifica_backups(retencao: &Retencao) 
+  -> io::Result<Vec<(Backup, bool)>>
+   { classifica_backups_em(&caminho_de("backups"), retencao) }
+
+fn classifica_backups_em(diretorio: &Path, retencao: &Retencao)
+  -> io::Result<Vec<(Backup, bool)>>
+{
+   let backups = lista_backups_em(diretorio)?;
+   let lista: Vec<(u64, u64)> = {
+      backups.iter()
+      .map(|b| (b.idade.as_secs(), b.tamanho))
+      .collect()
+   };
+   let manter = mantidos(&lista[..], retencao);
+
+   Ok(backups.into_iter().zip(manter).collect())
+}
+
+/** Apaga os backups que a política não mantém, e os retorna. Simulando,
+ apenas os retorna, nada é apagado. */
+pub fn poda_backups(retencao: &Retencao, simulado: bool) 
+  -> io::Result<Vec<Backup>>
+   { poda_backups_em(&caminho_de("backups"), retencao, simulado) }
+
+fn poda_backups_em(diretorio: &Path, retencao: &Retencao, simulado: bool)
+  -> io::Result<Vec<Backup>>
+{
+   let podados: Vec<Backup> = {
+      classifica_backups_em(diretorio, retencao)?
+      .into_iter()
+      .filter_map(|(b, fica)| if fica { None } else { Some(b) })
+      .collect()
+   };
+
+   if !simulado {
+      for b in podados.iter()
+         { remove_file(&b.caminho)?; }
+   }
+   Ok(podados)
 }
 
 
@@ -104,30 +244,27 @@ pub fn renomea(mut lista_ordenada: Entradas) {
 #[allow(non_snake_case)]
 mod tests {
    use super::*;
-   use std::fs::read_dir;
    use utilitarios::legivel::{tamanho, tempo as Tempo};
 
    /* tempo total desde sua criação. */
-   fn tempo(entrada: &DirEntry) -> u64{
-      let mt = entrada.metadata().unwrap();
-      return {
-         mt.created().unwrap()
-         .elapsed()
-         .unwrap().as_secs()
-      };
-   }
+   fn tempo(entrada: &DirEntry) -> u64
+      { decorrido(entrada).as_secs() }
 
 
    #[test]
    fn visualizaListagem() {
-      let caminho = "data/backups";
-      let entradas = read_dir(caminho).unwrap();
+      let caminho = caminho_de("backups");
+      let entradas = match read_dir(caminho) {
+         Ok(entradas) => entradas,
+         // sem backups, nada a visualizar.
+         Err(_) => return
+      };
       let lista = ordena(entradas, Ordenacao::Tamanho);
 
       for (i, e) in lista.iter().enumerate() {
          println!(
             "{0:>3.0}º ==> '{2}' ({1}/{3})",
-            i+1, tamanho(size(e) as u64, true), 
+            i+1, tamanho(size(e) as u64, true),
             nome(e), Tempo(tempo(e), true)
          );
       }
@@ -135,10 +272,68 @@ mod tests {
    }
 
    #[test]
-   fn prototipoDeRenomeacao() {
-      let caminho = "data/backups";
-      let entradas = read_dir(caminho).unwrap();
-      let lista = ordena(entradas, Ordenacao::Tempo);
-      renomea(lista); 
+   fn prototipoDeNumeracao() {
+      for b in lista_backups().unwrap()
+         { println!("{} >>> backup.{}.pacote", b.nome, b.rotulo); }
+   }
+
+   #[test]
+   fn RetencaoPorPeriodosETamanho() {
+      let retencao = Retencao {
+         ultimos: 2, diarios: 2, semanais: 1, mensais: 2,
+         tamanho_maximo: 1000
+      };
+      let hora = 60 * 60;
+      // do mais recente ao mais antigo: (idade, tamanho).
+      let lista = [
+         (0, 100), (hora, 100), (2 * hora, 100), (DIA + hora, 100),
+         (DIA + 2 * hora, 100), (3 * DIA, 100), (40 * DIA, 100),
+         (45 * DIA, 100), (100 * DIA, 100)
+      ];
+      assert_eq!(
+         mantidos(&lista, &retencao),
+         [true, true, false, true, false, false, true, false, false]
+      );
+
+      // o tamanho máximo corta os antigos, mas nunca o mais recente.
+      let retencao = Retencao { tamanho_maximo: 150, ..retencao };
+      assert_eq!(
+         mantidos(&lista, &retencao),
+         [true, false, false, false, false, false, false, false, false]
+      );
+      assert!(mantidos(&[], &retencao).is_empty());
+   }
+
+   #[test]
+   fn ZipAntigoTambemEPodado() {
+      use std::fs::{create_dir_all, remove_dir_all, write, File};
+      use std::time::SystemTime;
+
+      let diretorio = std::env::temp_dir().join("poda-zip-antigo");
+      let _ = remove_dir_all(&diretorio);
+      create_dir_all(&diretorio).unwrap();
+      let zip = diretorio.join("backup_bd_1641928218049.zip");
+      write(&zip, b"antigo").unwrap();
+      let ano = Duration::from_secs(400 * DIA);
+      let arquivo = File::options().write(true).open(&zip).unwrap();
+      arquivo.set_modified(SystemTime::now() - ano).unwrap();
+      write(diretorio.join("backup_bd_1.pacote"), b"novo").unwrap();
+      // o resto do diretório não é backup.
+      write(diretorio.join("manifesto.txt"), b"").unwrap();
+
+      let backups = lista_backups_em(&diretorio).unwrap();
+      let legados: Vec<bool> = backups.iter().map(|b| b.legado).collect();
+      assert_eq!(legados, vec![false, true]);
+
+      let retencao = Retencao { 
+         ultimos: 1, diarios: 0, semanais: 0, mensais: 0,
+         tamanho_maximo: u64::MAX
+      };
+      let podados = poda_backups_em(&diretorio, &retencao, false).unwrap();
+      assert_eq!(podados.len(), 1);
+      assert_eq!(podados[0].caminho, zip);
+      assert!(!zip.exists());
+      assert!(diretorio.join("backup_bd_1.pacote").exists());
+      remove_dir_all(&diretorio).unwrap();
    }
 }
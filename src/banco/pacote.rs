@@ -0,0 +1,191 @@
+/*! Pacote: um arquivo único que embrulha vários outros, usado pelos
+ backups. Não há compressão, apenas os conteúdos um atrás do outro, cada
+ um com o seu nome, tamanho e CRC-32; e o pacote inteiro fecha com um
+ CRC-32 de tudo que veio antes, assim um pacote cortado ou estragado é
+ sempre notado na leitura. Todos os inteiros são "little endian".
+
+    [ "PRIMOSPK" | versão(u16) | qtd. de arquivos(u16) | criação(u64) ]
+    [ nome(u16 + bytes) | tamanho(u64) | CRC-32(u32) | conteúdo ] ...
+    [ CRC-32 do pacote(u32) ]
+*/
+
+use super::formato::crc32;
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions, rename};
+use std::io::{self, Read, Write, Error, ErrorKind};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGICO_PACOTE: [u8; 8] = *b"PRIMOSPK";
+const VERSAO_PACOTE: u16 = 1;
+
+/// Um arquivo dentro do pacote: o seu nome, e todo o seu conteúdo.
+pub type Embrulho = (String, Vec<u8>);
+
+fn invalido(msg: String) -> Error
+   { Error::new(ErrorKind::InvalidData, msg) }
+
+/* Serializa todo o pacote em memória. */
+fn serializa_pacote(arquivos: &[Embrulho], criacao: u64) -> Vec<u8> {
+   let mut bytes = MAGICO_PACOTE.to_vec();
+   bytes.extend(VERSAO_PACOTE.to_le_bytes());
+   bytes.extend((arquivos.len() as u16).to_le_bytes());
+   bytes.extend(criacao.to_le_bytes());
+
+   for (nome, conteudo) in arquivos {
+      bytes.extend((nome.len() as u16).to_le_bytes());
+      bytes.extend(nome.as_bytes());
+      bytes.extend((conteudo.len() as u64).to_le_bytes());
+      bytes.extend(crc32(&conteudo[..]).to_le_bytes());
+      bytes.extend(&conteudo[..]);
+   }
+   let crc = crc32(&bytes[..]);
+   bytes.extend(crc.to_le_bytes());
+   bytes
+}
+
+/* Cursor simples sobre os bytes do pacote, com erro se acabar antes. */
+struct Leitura<'a> { bytes: &'a [u8], posicao: usize }
+
+impl<'a> Leitura<'a> {
+   fn pega(&mut self, n: usize) -> io::Result<&'a [u8]> {
+      let total = self.bytes.len();
+      match self.posicao.checked_add(n).filter(|&fim| fim <= total) {
+         Some(fim) => {
+            let fatia = &self.bytes[self.posicao..fim];
+            self.posicao = fim;
+            Ok(fatia)
+         } None =>
+            { Err(invalido(String::from("pacote cortado"))) }
+      }
+   }
+
+   fn u16(&mut self) -> io::Result<u16>
+      { Ok(u16::from_le_bytes(self.pega(2)?.try_into().unwrap())) }
+   fn u32(&mut self) -> io::Result<u32>
+      { Ok(u32::from_le_bytes(self.pega(4)?.try_into().unwrap())) }
+   fn u64(&mut self) -> io::Result<u64>
+      { Ok(u64::from_le_bytes(self.pega(8)?.try_into().unwrap())) }
+}
+
+/* Lê e confere um pacote inteiro que já está em memória. Retorna a data
+ * de criação(segundos desde a época UNIX) e os arquivos embrulhados. */
+fn deserializa_pacote(bytes: &[u8]) -> io::Result<(u64, Vec<Embrulho>)> {
+   if bytes.len() < 8 + 2 + 2 + 8 + 4 || bytes[0..8] != MAGICO_PACOTE
+      { return Err(invalido(String::from("não é um pacote de backup"))); }
+
+   let (corpo, cauda) = bytes.split_at(bytes.len() - 4);
+   if crc32(corpo) != u32::from_le_bytes(cauda.try_into().unwrap())
+      { return Err(invalido(String::from("CRC do pacote não confere"))); }
+
+   let mut leitura = Leitura { bytes: corpo, posicao: 8 };
+   let versao = leitura.u16()?;
+   if versao != VERSAO_PACOTE
+   {
+      let msg = format!("versão {} de pacote desconhecida", versao);
+      return Err(invalido(msg)); 
+   }
+   let qtd = leitura.u16()?;
+   let criacao = leitura.u64()?;
+   let mut arquivos = Vec::with_capacity(qtd as usize);
+
+   for _ in 0..qtd {
+      let tamanho_nome = leitura.u16()? as usize;
+      let nome = String::from_utf8(leitura.pega(tamanho_nome)?.to_vec())
+         .map_err(|_| invalido(String::from("nome de arquivo inválido")))?;
+      let tamanho = leitura.u64()? as usize;
+      let crc = leitura.u32()?;
+      let conteudo = leitura.pega(tamanho)?.to_vec();
+
+      if crc32(&conteudo[..]) != crc {
+         let msg = format!("'{}' está corrompido no pacote", nome);
+         return Err(invalido(msg)); 
+      }
+      arquivos.push((nome, conteudo));
+   }
+   if leitura.posicao != corpo.len()
+      { return Err(invalido(String::from("sobras no final do pacote"))); }
+   Ok((criacao, arquivos))
+}
+
+/** Grava o pacote com os arquivos dados no destino, de forma atômica: um
+ temporário é escrito e forçado ao disco, e só então renomeado. */
+pub fn escreve_pacote(destino: &Path, arquivos: &[Embrulho]) -> io::Result<()> {
+   let criacao = {
+      SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |d| d.as_secs())
+   };
+   let temporario = destino.with_extension("novo");
+   let mut arquivo = {
+      OpenOptions::new()
+      .create(true).write(true).truncate(true)
+      .open(&temporario)?
+   };
+
+   arquivo.write_all(&serializa_pacote(arquivos, criacao)[..])?;
+   arquivo.sync_all()?;
+   drop(arquivo);
+   rename(&temporario, destino)?;
+   // garantindo que a renomeação também foi para o disco.
+   if let Some(pai) = destino.parent()
+      { File::open(pai)?.sync_all()?; }
+   Ok(())
+}
+
+/** Lê, e confere todos os CRCs, do pacote dado. Retorna a data de criação
+ e os arquivos embrulhados. */
+pub fn le_pacote(origem: &Path) -> io::Result<(u64, Vec<Embrulho>)> {
+   let mut bytes = Vec::new();
+   File::open(origem)?.read_to_end(&mut bytes)?;
+   deserializa_pacote(&bytes[..])
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use std::env::temp_dir;
+   use std::fs::{remove_file, write};
+
+   fn amostra() -> Vec<Embrulho> {
+      let conteudo: Vec<u8> = (0..=255u8).cycle().take(5000).collect();
+      vec![
+         (String::from("banco_de_dados.dat"), conteudo),
+         (String::from("ultima_insercao.dat"), vec![118, 0, 0, 0]),
+         (String::from("vazio.dat"), Vec::new())
+      ]
+   }
+
+   #[test]
+   fn PacoteIdaEVolta() {
+      let caminho = temp_dir().join("pacote-ida-e-volta.pacote");
+      escreve_pacote(&caminho, &amostra()[..]).unwrap();
+
+      let (criacao, arquivos) = le_pacote(&caminho).unwrap();
+      assert!(criacao > 0);
+      assert_eq!(arquivos, amostra());
+      remove_file(caminho).unwrap();
+   }
+
+   #[test]
+   fn PacoteEstragadoOuCortadoERejeitado() {
+      let bytes = serializa_pacote(&amostra()[..], 1);
+      assert!(deserializa_pacote(&bytes[..]).is_ok());
+
+      let mut estragado = bytes.clone();
+      estragado[100] ^= 0x01;
+      assert!(deserializa_pacote(&estragado[..]).is_err());
+      assert!(deserializa_pacote(&bytes[..bytes.len() - 9]).is_err());
+      assert!(deserializa_pacote(b"isto nao e um pacote").is_err());
+
+      let caminho = temp_dir().join("pacote-estragado.pacote");
+      write(&caminho, &estragado[..]).unwrap();
+      assert_eq!(
+         le_pacote(&caminho).unwrap_err().kind(),
+         ErrorKind::InvalidData
+      );
+      remove_file(caminho).unwrap();
+   }
+}
@@ -0,0 +1,234 @@
+/*! Recuperação do banco de dados após uma queda do programa no meio de
+ uma gravação. Como cada registro é prefixado pelo seu tamanho e termina
+ com o seu CRC-32, um registro só "existe" quando foi escrito por inteiro,
+ ou seja, o CRC é o marcador de que a inserção foi efetivada. Um registro
+ cortado, ou inválido, bem no final do arquivo é apenas um rastro de uma
+ gravação interrompida; este é descartado, e o índice de última inserção
+ é reconstruído com o total real de registros.
+*/
+
+use super::formato::{
+   caminho_bd, le_registro, crc32, crc_de_bytes, de_bytes, Cabecalho,
+   TAMANHO_CABECALHO
+};
+use super::ultima_insercao::{indice_gravado, atualiza_indice_de_insercao};
+use super::ByteOrdem;
+use std::convert::TryInto;
+use std::fs::OpenOptions;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Error, ErrorKind};
+use std::path::Path;
+
+/// O que foi encontrado(e feito) na passagem de recuperação.
+#[derive(Debug, PartialEq)]
+pub struct Recuperacao {
+   // total de registros íntegros.
+   pub registros: u32,
+   // bytes descartados do final do arquivo.
+   pub descartado: u64
+}
+
+/* Tamanho que o registro, começando na posição dada, declara ter(já com o
+ * prefixo e o CRC). Nada se nem o prefixo está inteiro. */
+fn tamanho_declarado<R: Read + Seek>(leitor: &mut R, posicao: u64,
+  ordem: ByteOrdem) -> io::Result<Option<u64>>
+{
+   let mut bytes = [0u8; 8];
+   leitor.seek(SeekFrom::Start(posicao))?;
+
+   match leitor.read_exact(&mut bytes) {
+      Ok(_) => {
+         let tamanho = match ordem {
+            ByteOrdem::LittleEndian => u64::from_le_bytes(bytes),
+            ByteOrdem::BigEndian => u64::from_be_bytes(bytes)
+         };
+         Ok(Some(tamanho.saturating_add(8 + 4)))
+      } Err(_) => Ok(None)
+   }
+}
+
+/* Se há, em algum ponto depois do começo dos bytes dados, um registro
+ * íntegro: um prefixo de tamanho que cabe no que resta, seguido do
+ * conteúdo e de um CRC que confere. */
+fn ha_registro_adiante(bytes: &[u8], ordem: ByteOrdem) -> bool {
+   (1..bytes.len()).any(|k| {
+      let resto = &bytes[k..];
+      if resto.len() < 8 + 4
+         { return false; }
+      let prefixo: [u8; 8] = resto[..8].try_into().unwrap();
+      let tamanho = de_bytes(prefixo, ordem);
+      // sem conteúdo não é registro, só zeros.
+      if tamanho == 0 || tamanho > (resto.len() - 8 - 4) as u64
+         { return false; }
+      let fim = 8 + tamanho as usize;
+      let crc: [u8; 4] = resto[fim..fim + 4].try_into().unwrap();
+      crc_de_bytes(crc, ordem) == crc32(&resto[8..fim])
+   })
+}
+
+/** Varre todos registros do arquivo dado, e corta o final se este for um
+ registro incompleto. Um registro inválido que não seja o último, não é
+ fruto de uma gravação interrompida, então é reportado como erro, e nada
+ é alterado. */
+pub fn recupera_arquivo(caminho: &Path) -> io::Result<Recuperacao> {
+   let arquivo = OpenOptions::new().read(true).write(true).open(caminho)?;
+   let tamanho = arquivo.metadata()?.len();
+   let mut resultado = Recuperacao { registros: 0, descartado: 0 };
+
+   // cabeçalho cortado: o arquivo nunca chegou a ter algum registro.
+   if tamanho < TAMANHO_CABECALHO {
+      if tamanho > 0 {
+         arquivo.set_len(0)?;
+         arquivo.sync_all()?;
+         resultado.descartado = tamanho;
+      }
+      return Ok(resultado);
+   }
+
+   let mut leitor = BufReader::new(&arquivo);
+   let mut bytes = [0u8; TAMANHO_CABECALHO as usize];
+   leitor.read_exact(&mut bytes)?;
+   let cabecalho = Cabecalho::deserializa(&bytes[..])?;
+   let ordem = cabecalho.ordem;
+   let mut posicao = TAMANHO_CABECALHO;
+
+   loop {
+      match le_registro(&mut leitor, &cabecalho) {
+         Ok(Some(_)) => {
+            resultado.registros += 1;
+            posicao = leitor.stream_position()?;
+         } Ok(None) =>
+            { break; }
+         Err(erro) => {
+            let fim = {
+               tamanho_declarado(&mut leitor, posicao, ordem)?
+               .and_then(|t| posicao.checked_add(t))
+            };
+            let mut resto = Vec::new();
+            leitor.seek(SeekFrom::Start(posicao))?;
+            leitor.read_to_end(&mut resto)?;
+            /* só o último registro pode estar pela metade: se termina antes
+             * do arquivo, ou se há outro íntegro depois dele, o estrago é
+             * no meio, e cortar ali perderia registros bons. */
+            let no_meio = {
+               fim.is_some_and(|f| f < tamanho)
+               || ha_registro_adiante(&resto[..], ordem)
+            };
+            if no_meio {
+               let msg = format!(
+                  "registro nº.{} corrompido no meio do arquivo: {}",
+                  resultado.registros + 1, erro
+               );
+               return Err(Error::new(ErrorKind::InvalidData, msg));
+            }
+            drop(leitor);
+            arquivo.set_len(posicao)?;
+            arquivo.sync_all()?;
+            resultado.descartado = tamanho - posicao;
+            break;
+         }
+      };
+   }
+   Ok(resultado)
+}
+
+/** Passagem de recuperação do banco de dados: descarta um possível registro
+ incompleto no final, e acerta o índice de última inserção. */
+pub fn recupera_bd() -> io::Result<Recuperacao> {
+   let resultado = match recupera_arquivo(&caminho_bd()) {
+      Ok(r) => r,
+      Err(ref erro) if erro.kind() == ErrorKind::NotFound =>
+         { return Ok(Recuperacao { registros: 0, descartado: 0 }); }
+      Err(erro) => return Err(erro)
+   };
+
+   if resultado.descartado > 0 {
+      println!(
+         "gravação interrompida detectada, {} bytes descartados do BD.",
+         resultado.descartado
+      );
+   }
+   if indice_gravado() != Some(resultado.registros)
+      { atualiza_indice_de_insercao(resultado.registros); }
+   Ok(resultado)
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use super::super::formato::serializa_busca;
+   use std::env::temp_dir;
+   use std::fs::{write, remove_file, metadata};
+   use std::path::PathBuf;
+
+   fn arquivo_de_teste(nome: &str, registros: usize) -> (PathBuf, Vec<u8>) {
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+      let caminho = temp_dir().join(nome);
+      let mut bytes = cabecalho.serializa().to_vec();
+
+      for k in 0..registros {
+         let busca = (vec![2, 3, 5, 7, 11], 12 + k as u64, 1, 1);
+         bytes.extend(serializa_busca(&busca, &cabecalho));
+      }
+      write(&caminho, &bytes[..]).unwrap();
+      (caminho, bytes)
+   }
+
+   #[test]
+   fn ArquivoIntegroNaoEAlterado() {
+      let (caminho, bytes) = arquivo_de_teste("recupera-integro.dat", 3);
+      let r = recupera_arquivo(&caminho).unwrap();
+
+      assert_eq!(r, Recuperacao { registros: 3, descartado: 0 });
+      assert_eq!(metadata(&caminho).unwrap().len(), bytes.len() as u64);
+      remove_file(caminho).unwrap();
+   }
+
+   #[test]
+   fn CortaRegistroIncompleto() {
+      let (caminho, bytes) = arquivo_de_teste("recupera-cortado.dat", 3);
+      let integro = bytes.len() as u64;
+      // simulando uma queda no meio do quarto registro.
+      let mut cortado = bytes.clone();
+      let busca = (vec![13, 17, 19], 20, 1, 1);
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+      let quarto = serializa_busca(&busca, &cabecalho);
+      cortado.extend(&quarto[..quarto.len() - 7]);
+      write(&caminho, &cortado[..]).unwrap();
+
+      let r = recupera_arquivo(&caminho).unwrap();
+      assert_eq!(r.registros, 3);
+      assert_eq!(r.descartado, quarto.len() as u64 - 7);
+      assert_eq!(metadata(&caminho).unwrap().len(), integro);
+      remove_file(caminho).unwrap();
+   }
+
+   #[test]
+   fn CorrupcaoNoMeioNaoEDescartada() {
+      let (caminho, mut bytes) = arquivo_de_teste("recupera-meio.dat", 3);
+      // estraga um primo do primeiro registro.
+      bytes[TAMANHO_CABECALHO as usize + 20] ^= 0xff;
+      write(&caminho, &bytes[..]).unwrap();
+
+      assert!(recupera_arquivo(&caminho).is_err());
+      assert_eq!(metadata(&caminho).unwrap().len(), bytes.len() as u64);
+      remove_file(caminho).unwrap();
+   }
+
+   #[test]
+   fn PrefixoCorrompidoNoMeioNaoCorta() {
+      let (caminho, mut bytes) = arquivo_de_teste("recupera-prefixo.dat", 4);
+      let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+      let registro = serializa_busca(&(vec![2, 3, 5, 7, 11], 12, 1, 1), &cabecalho);
+      // o segundo registro declara ir além do fim do arquivo.
+      let segundo = TAMANHO_CABECALHO as usize + registro.len();
+      bytes[segundo..segundo + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+      write(&caminho, &bytes[..]).unwrap();
+
+      let erro = recupera_arquivo(&caminho).unwrap_err();
+      assert_eq!(erro.kind(), ErrorKind::InvalidData);
+      assert_eq!(metadata(&caminho).unwrap().len(), bytes.len() as u64);
+      remove_file(caminho).unwrap();
+   }
+}
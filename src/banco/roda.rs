@@ -0,0 +1,448 @@
+/*! Modo "roda" do banco de dados: as faixas varridas ficam num mapa de
+ bits da roda de 30, onde cada byte cobre 30 números, e cada bit um dos oito
+ restos que não são múltiplos de 2, 3 ou 5(1, 7, 11, 13, 17, 19, 23 e 29).
+ Uma faixa densa de primos ocupa assim uns 1,4 bytes por primo, contra os
+ 8 da lista, e dizer se um número está nela é só olhar um bit.
+
+ O arquivo 'banco_roda.dat' troca o 'banco_de_dados.dat' por inteiro, com
+ um cabeçalho de 16 bytes("PRIMOSRD", e a versão em u16) e os registros
+ no mesmo esquema do formato(tamanho, conteúdo e CRC-32; tudo em
+ little-endian). Cada registro é um segmento, no mapa de bits ou, quando
+ não compensa(poucos primos na faixa) ou não cabe(fora de ordem, números
+ divisíveis por 2, 3 ou 5, ...), na lista de sempre. Assim a conversão
+ entre os modos nunca perde nada.
+
+ Enquanto o BD está neste modo, apenas a leitura dele e o anexo de novas
+ varreduras funcionam; o restante pede que volte para a lista antes.
+*/
+
+use super::{Busca, caminho_de, carrega_bd, NOME_BD};
+use super::cobertura::faixas_de;
+use super::formato::{crc32, reescreve_bd, codec_padrao};
+use super::indice_primos::reconstroi_indice;
+use super::realiza_backup_bd;
+use super::ByteOrdem;
+use std::convert::{TryFrom, TryInto};
+use std::fs::{OpenOptions, metadata, read, remove_file, rename};
+use std::io::{self, Write, Error, ErrorKind};
+use std::path::PathBuf;
+
+/// Nome do arquivo do BD no modo roda.
+pub const NOME_RODA: &str = "banco_roda.dat";
+// Identificação do arquivo, e a sua versão.
+const MAGICO: [u8; 8] = *b"PRIMOSRD";
+const VERSAO: u16 = 1;
+const TAMANHO_CABECALHO: usize = 16;
+// Restos, módulo 30, que a roda guarda; e o bit de cada resto.
+const RESTOS: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+const BIT_DO_RESTO: [u8; 30] = {
+   let mut tabela = [u8::MAX; 30];
+   let mut i = 0;
+   while i < 8
+      { tabela[RESTOS[i] as usize] = i as u8; i += 1; }
+   tabela
+};
+// Os primos fora da roda.
+const PEQUENOS: [u64; 3] = [2, 3, 5];
+
+/// Um registro do BD no modo roda.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segmento {
+   /* Faixa [inicio, fim] com os primos num mapa de bits, tirando o 2, o 3
+    * e o 5, que ficam em 'pequenos'(um bit para cada). */
+   Roda {
+      inicio: u64, fim: u64, ttc: u64, qb: u64,
+      pequenos: u8, mapa: Vec<u8>
+   },
+   // Registro que não vale(ou não dá) para pôr no mapa.
+   Lista(Busca)
+}
+
+fn mal_formado() -> Error {
+   let msg = "segmento do BD no modo roda mal formado";
+   Error::new(ErrorKind::InvalidData, msg)
+}
+
+/* Se o número tem um bit na roda(o 1 tem, mas não é primo). */
+fn na_roda(n: u64) -> bool
+   { n > 1 && BIT_DO_RESTO[(n % 30) as usize] != u8::MAX }
+
+/* Bytes que o mapa de bits da faixa dada ocupa. */
+fn blocos(inicio: u64, fim: u64) -> u64
+   { fim / 30 - inicio / 30 + 1 }
+
+/* Só vão para a roda primos em ordem estritamente crescente, todos dentro
+ * da faixa, e fora os pequenos, nenhum múltiplo de 2, 3 ou 5. E só se o
+ * mapa não ficar maior que a lista. */
+fn cabe_na_roda(primos: &[u64], inicio: u64, fim: u64) -> bool {
+   inicio <= fim
+   && blocos(inicio, fim) <= 8 * primos.len() as u64
+   && primos.windows(2).all(|par| par[0] < par[1])
+   && primos.iter().all(|&p| {
+      (inicio..=fim).contains(&p)
+      && (PEQUENOS.contains(&p) || na_roda(p))
+   })
+}
+
+impl Segmento {
+   /** O segmento de uma busca cuja faixa começa em `inicio`. */
+   pub fn de_busca(busca: &Busca, inicio: u64) -> Self {
+      let (primos, fim, ttc, qb) = busca.clone();
+
+      if !cabe_na_roda(&primos[..], inicio, fim)
+         { return Segmento::Lista((primos, fim, ttc, qb)); }
+      let base = inicio / 30;
+      let mut mapa = vec![0u8; blocos(inicio, fim) as usize];
+      let mut pequenos = 0u8;
+
+      for p in primos {
+         match PEQUENOS.iter().position(|&q| q == p) {
+            Some(i) => pequenos |= 1 << i,
+            None => {
+               let bit = BIT_DO_RESTO[(p % 30) as usize];
+               mapa[(p / 30 - base) as usize] |= 1 << bit;
+            }
+         }
+      }
+      Segmento::Roda { inicio, fim, ttc, qb, pequenos, mapa }
+   }
+
+   /** A busca de volta, tal qual era na lista. */
+   pub fn para_busca(&self) -> Busca {
+      match self {
+         Segmento::Lista(busca) => busca.clone(),
+         Segmento::Roda { inicio, fim, ttc, qb, pequenos, mapa } => {
+            let base = inicio / 30;
+            let mut primos: Vec<u64> = {
+               (0..3).filter(|i| pequenos & (1 << i) != 0)
+               .map(|i| PEQUENOS[i])
+               .collect()
+            };
+            for (j, &byte) in mapa.iter().enumerate() {
+               let bloco = (base + j as u64) * 30;
+               primos.extend(
+                  (0..8).filter(|i| byte & (1 << i) != 0)
+                  .map(|i| bloco + RESTOS[i])
+               );
+            }
+            (primos, *fim, *ttc, *qb)
+         }
+      }
+   }
+
+   /** Se o número dado está no segmento: nada se estiver fora da faixa
+    dele; no mapa, é só olhar o seu bit. */
+   pub fn contem(&self, n: u64) -> Option<bool> {
+      match self {
+         Segmento::Lista((primos, ..)) => {
+            match (primos.first(), primos.last()) {
+               (Some(&a), Some(&b)) if a <= n && n <= b =>
+                  { Some(primos.contains(&n)) }
+               _ => None
+            }
+         }
+         Segmento::Roda { inicio, fim, pequenos, mapa, .. } => {
+            if n < *inicio || n > *fim
+               { return None; }
+            if let Some(i) = PEQUENOS.iter().position(|&q| q == n)
+               { return Some(pequenos & (1 << i) != 0); }
+            if !na_roda(n)
+               { return Some(false); }
+            let bit = BIT_DO_RESTO[(n % 30) as usize];
+            let byte = mapa[(n / 30 - inicio / 30) as usize];
+            Some(byte & (1 << bit) != 0)
+         }
+      }
+   }
+
+   /** Fim da faixa do segmento(o último número verificado). */
+   pub fn fim(&self) -> u64 {
+      match self {
+         Segmento::Lista(busca) => busca.1,
+         Segmento::Roda { fim, .. } => *fim
+      }
+   }
+
+   fn serializa(&self) -> Vec<u8> {
+      let mut conteudo = Vec::new();
+      let poe = |valores: &[u64], bytes: &mut Vec<u8>| {
+         for v in valores
+            { bytes.extend(&v.to_le_bytes()[..]); }
+      };
+
+      match self {
+         Segmento::Lista((primos, unv, ttc, qb)) => {
+            conteudo.push(0);
+            poe(&[*unv, *ttc, *qb, primos.len() as u64], &mut conteudo);
+            poe(&primos[..], &mut conteudo);
+         }
+         Segmento::Roda { inicio, fim, ttc, qb, pequenos, mapa } => {
+            conteudo.push(1);
+            poe(&[*inicio, *fim, *ttc, *qb], &mut conteudo);
+            conteudo.push(*pequenos);
+            conteudo.extend(&mapa[..]);
+         }
+      };
+      let mut registro = (conteudo.len() as u64).to_le_bytes().to_vec();
+      registro.extend(&conteudo[..]);
+      registro.extend(&crc32(&conteudo[..]).to_le_bytes()[..]);
+      registro
+   }
+
+   fn deserializa(conteudo: &[u8]) -> io::Result<Self> {
+      // o tipo, e então quatro valores de 8 bytes.
+      let valor = |i: usize| -> io::Result<u64> {
+         let bytes = conteudo.get(1 + 8 * i..9 + 8 * i);
+         let bytes = bytes.ok_or_else(mal_formado)?;
+         Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+      };
+      let resto = conteudo.get(33..).ok_or_else(mal_formado)?;
+
+      match conteudo[0] {
+         0 => {
+            let qtd = valor(3)?;
+            if resto.len() % 8 != 0 || (resto.len() / 8) as u64 != qtd
+               { return Err(mal_formado()); }
+            let primos = {
+               resto.chunks_exact(8)
+               .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+               .collect()
+            };
+            Ok(Segmento::Lista((primos, valor(0)?, valor(1)?, valor(2)?)))
+         }
+         1 => {
+            let (inicio, fim) = (valor(0)?, valor(1)?);
+            let (&pequenos, mapa) = resto.split_first().ok_or_else(mal_formado)?;
+            if inicio > fim || pequenos > 0b111
+              || blocos(inicio, fim) != mapa.len() as u64
+               { return Err(mal_formado()); }
+            Ok(Segmento::Roda {
+               inicio, fim, ttc: valor(2)?, qb: valor(3)?,
+               pequenos, mapa: mapa.to_vec()
+            })
+         }
+         _ => Err(mal_formado())
+      }
+   }
+}
+
+/* Segmentos de um arquivo inteiro do modo roda, já em memória. */
+fn segmentos_de(bytes: &[u8]) -> io::Result<Vec<Segmento>> {
+   if bytes.len() < TAMANHO_CABECALHO || bytes[0..8] != MAGICO {
+      let msg = "não é um banco de dados de primos no modo roda";
+      return Err(Error::new(ErrorKind::InvalidData, msg));
+   }
+   let versao = u16::from_le_bytes([bytes[8], bytes[9]]);
+   if versao != VERSAO {
+      let msg = format!("versão {} do modo roda não suportada", versao);
+      return Err(Error::new(ErrorKind::InvalidData, msg));
+   }
+   let mut resto = &bytes[TAMANHO_CABECALHO..];
+   let mut segmentos = Vec::new();
+
+   while !resto.is_empty() {
+      let tamanho = resto.get(0..8).ok_or_else(mal_formado)?;
+      let tamanho = u64::from_le_bytes(tamanho.try_into().unwrap());
+      let tamanho = usize::try_from(tamanho).map_err(|_| mal_formado())?;
+      let fim = tamanho.checked_add(12).filter(|&f| f <= resto.len());
+      let fim = fim.ok_or_else(mal_formado)?;
+      let (conteudo, crc) = resto[8..fim].split_at(tamanho);
+
+      if u32::from_le_bytes(crc.try_into().unwrap()) != crc32(conteudo) {
+         let msg = "CRC-32 do segmento não confere, dados corrompidos";
+         return Err(Error::new(ErrorKind::InvalidData, msg));
+      }
+      segmentos.push(Segmento::deserializa(conteudo)?);
+      resto = &resto[fim..];
+   }
+   Ok(segmentos)
+}
+
+/** Confere um BD no modo roda já em memória(o de um backup). Retorna a
+ quantia de segmentos. */
+pub fn valida_roda(bytes: &[u8]) -> io::Result<usize>
+   { segmentos_de(bytes).map(|s| s.len()) }
+
+fn caminho_roda() -> PathBuf
+   { caminho_de(NOME_RODA) }
+
+/** Se o BD está no modo roda: o seu arquivo existe, e o da lista não. */
+pub fn modo_roda() -> bool
+   { caminho_roda().is_file() && !caminho_de(NOME_BD).exists() }
+
+/** Todos segmentos do BD no modo roda. */
+pub fn le_roda() -> io::Result<Vec<Segmento>>
+   { segmentos_de(&read(caminho_roda())?[..]) }
+
+/** As buscas do BD no modo roda, como se estivessem na lista. */
+pub fn carrega_roda() -> io::Result<Vec<Busca>>
+   { Ok(le_roda()?.iter().map(Segmento::para_busca).collect()) }
+
+/* Grava, de forma atômica, todo o arquivo do modo roda. */
+fn grava_roda(segmentos: &[Segmento]) -> io::Result<()> {
+   let caminho = caminho_roda();
+   let temporario = caminho.with_extension("dat.novo");
+   let mut cabecalho = [0u8; TAMANHO_CABECALHO];
+   cabecalho[0..8].copy_from_slice(&MAGICO[..]);
+   cabecalho[8..10].copy_from_slice(&VERSAO.to_le_bytes()[..]);
+   let mut arquivo = {
+      OpenOptions::new()
+      .create(true).write(true).truncate(true)
+      .open(&temporario)?
+   };
+
+   arquivo.write_all(&cabecalho[..])?;
+   for segmento in segmentos
+      { arquivo.write_all(&segmento.serializa()[..])?; }
+   arquivo.sync_all()?;
+   drop(arquivo);
+   rename(&temporario, &caminho)
+}
+
+/** Anexa uma nova busca ao BD no modo roda. A sua faixa começa logo após
+ o último segmento(ou no seu primeiro primo, se este vier antes). */
+pub fn anexa_na_roda(busca: &Busca) -> io::Result<()> {
+   let anterior = le_roda()?.last().map(Segmento::fim);
+   let inicio = {
+      let seguinte = anterior.map_or(0, |f| f.saturating_add(1));
+      busca.0.first().map_or(seguinte, |&p| p.min(seguinte))
+   };
+   let segmento = Segmento::de_busca(busca, inicio);
+   let mut arquivo = OpenOptions::new().append(true).open(caminho_roda())?;
+
+   arquivo.write_all(&segmento.serializa()[..])?;
+   arquivo.sync_data()
+}
+
+/** Diz se o número dado está entre os primos do BD no modo roda. */
+pub fn contem_na_roda(n: u64) -> io::Result<bool> {
+   let segmentos = le_roda()?;
+   Ok(segmentos.iter().any(|s| s.contem(n) == Some(true)))
+}
+
+/** Converte o BD da lista para o modo roda, depois de um backup. Retorna
+ quantos segmentos foram para o mapa de bits, e quantos ficaram na lista. */
+pub fn converte_para_roda() -> io::Result<(usize, usize)> {
+   if modo_roda()
+      { return Err(Error::other("o BD já está no modo roda")); }
+   let bd = carrega_bd()?;
+   let buscas: Vec<Busca> = {
+      (1..=bd.len() as u32)
+      .filter_map(|i| bd.get(&i).cloned())
+      .collect()
+   };
+   drop(bd);
+   let caminho = realiza_backup_bd()?;
+   println!("backup de antes da conversão em {}", caminho.display());
+
+   let segmentos: Vec<Segmento> = {
+      faixas_de(buscas.iter()).into_iter()
+      .zip(buscas.iter())
+      .map(|(faixa, busca)| Segmento::de_busca(busca, *faixa.start()))
+      .collect()
+   };
+   // a conversão tem que ser exata, senão nem troca os arquivos.
+   if segmentos.iter().zip(buscas.iter()).any(|(s, b)| s.para_busca() != *b) {
+      let msg = "a conversão para a roda não confere com o BD";
+      return Err(Error::new(ErrorKind::InvalidData, msg));
+   }
+   grava_roda(&segmentos[..])?;
+   remove_file(caminho_de(NOME_BD))?;
+   // o índice de primos só serve para a lista.
+   let _ = remove_file(caminho_de("indice_primos.dat"));
+
+   let na_roda = {
+      segmentos.iter()
+      .filter(|s| matches!(s, Segmento::Roda { .. }))
+      .count()
+   };
+   Ok((na_roda, segmentos.len() - na_roda))
+}
+
+/** Converte o BD do modo roda de volta para a lista, na ordem de bytes
+ padrão e na codificação atual. Retorna a quantia de registros. */
+pub fn converte_para_lista() -> io::Result<usize> {
+   if !modo_roda()
+      { return Err(Error::other("o BD não está no modo roda")); }
+   let buscas = carrega_roda()?;
+
+   // a lista é gravada antes de apagar a roda; nada se perde no meio.
+   reescreve_bd(&buscas[..], ByteOrdem::LittleEndian)?;
+   remove_file(caminho_roda())?;
+   reconstroi_indice()?;
+   println!("lista gravada na codificação {:?}.", codec_padrao());
+   Ok(buscas.len())
+}
+
+/** Tamanho do arquivo do BD no modo roda. */
+pub fn tamanho_roda() -> io::Result<u64>
+   { metadata(caminho_roda()).map(|m| m.len()) }
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use crate::motor::crivo_segmentado;
+
+   fn ida_e_volta(segmento: &Segmento) -> Segmento {
+      let registro = segmento.serializa();
+      Segmento::deserializa(&registro[8..registro.len() - 4]).unwrap()
+   }
+
+   #[test]
+   fn FaixaDensaVaiParaARoda() {
+      let primos = crivo_segmentado(0, 99_999);
+      let busca: Busca = (primos.clone(), 99_999, 3, 1000);
+      let segmento = Segmento::de_busca(&busca, 0);
+
+      assert!(matches!(segmento, Segmento::Roda { .. }));
+      assert_eq!(segmento.para_busca(), busca);
+      assert_eq!(ida_e_volta(&segmento), segmento);
+      // um byte para cada 30 números.
+      assert!(segmento.serializa().len() < 8 * primos.len() / 5);
+
+      for n in 0..100_000 {
+         let esperado = primos.binary_search(&n).is_ok();
+         assert_eq!(segmento.contem(n), Some(esperado));
+      }
+      assert_eq!(segmento.contem(100_000), None);
+   }
+
+   #[test]
+   fn OQueNaoCabeFicaNaLista() {
+      let casos: Vec<(Busca, u64)> = vec![
+         // fora de ordem.
+         ((vec![7, 11, 13, 5], 20, 1, 1), 0),
+         // múltiplo de 3.
+         ((vec![7, 9, 11], 12, 1, 1), 7),
+         // antes do ínicio da faixa.
+         ((vec![97, 101], 110, 1, 1), 100),
+         // faixa longa demais para tão poucos primos.
+         ((vec![1_000_003], 9_000_000, 1, 1), 1_000_003),
+         ((vec![], 42, 1, 1), 30)
+      ];
+      for (busca, inicio) in casos {
+         let segmento = Segmento::de_busca(&busca, inicio);
+         assert_eq!(segmento, Segmento::Lista(busca.clone()));
+         assert_eq!(ida_e_volta(&segmento).para_busca(), busca);
+      }
+   }
+
+   #[test]
+   fn ArquivoEstragadoERejeitado() {
+      let mut bytes = MAGICO.to_vec();
+      bytes.extend(&VERSAO.to_le_bytes()[..]);
+      bytes.resize(TAMANHO_CABECALHO, 0);
+      let busca: Busca = (crivo_segmentado(30, 300), 300, 1, 1);
+      bytes.extend(Segmento::de_busca(&busca, 30).serializa());
+      bytes.extend(Segmento::Lista(busca.clone()).serializa());
+
+      assert_eq!(valida_roda(&bytes[..]).unwrap(), 2);
+      assert!(valida_roda(&bytes[..bytes.len() - 1]).is_err());
+      let mut estragado = bytes.clone();
+      estragado[30] ^= 1;
+      assert!(valida_roda(&estragado[..]).is_err());
+      assert!(valida_roda(&bytes[1..]).is_err());
+   }
+}
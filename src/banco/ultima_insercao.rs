@@ -10,11 +10,10 @@
 
 // biblioteca padrão:
 use std::fs::OpenOptions;
-use std::path::{Path, PathBuf};
 use std::io::{Write, Read};
 
 // puxando do módulo.
-use super::{carrega_bd, DIR};
+use super::{carrega_bd, caminho_de};
 
 // local do arquivo que será lido e atualizado.
 const REGISTROS_BD:&str = "ultima_insercao.dat";
@@ -25,9 +24,7 @@ pub fn ultima_insercao_feita() -> Option<u32> {
    /* tenta abrir o banco de dados, se não houver 
     * algum, criar e colocar o último índice de
     * inserção nele. */
-   let mut caminho = PathBuf::new();
-   caminho.push(DIR);
-   caminho.push(REGISTROS_BD);
+   let caminho = caminho_de(REGISTROS_BD);
    let arquivo = {
       OpenOptions::new()
       .read(true)
@@ -75,6 +72,17 @@ pub fn ultima_insercao_feita() -> Option<u32> {
    };
 }
 
+/* índice gravado no arquivo, sem qualquer tentativa de recriar ele caso 
+ * não exista. */
+pub fn indice_gravado() -> Option<u32> {
+   let caminho = caminho_de(REGISTROS_BD);
+   let mut bytes = [u8::MAX; 4];
+   let mut arquivo = OpenOptions::new().read(true).open(caminho).ok()?;
+
+   arquivo.read_exact(&mut bytes).ok()?;
+   Some(u32::from_le_bytes(bytes))
+}
+
 /* apenas faz isso se houver trinta ou mais inserções
  * a mais ou, o tamanho atual do BD for 30% superior
  */
@@ -85,7 +93,7 @@ pub fn atualiza_indice_de_insercao(indice:u32) {
       OpenOptions::new()
       .write(true)
       .create(true)
-      .open(Path::new(DIR).join(REGISTROS_BD))
+      .open(caminho_de(REGISTROS_BD))
    };
    match arquivo {
       Ok(mut file) => {
@@ -0,0 +1,106 @@
+/*! Rascunho de uma varredura em andamento. As buscas longas salvam de
+ tempos em tempos o que já acharam aqui, assim se o programa cair(ou for
+ morto) no meio, o trabalho feito não se perde: na próxima execução é
+ possível retomá-la, ou gravar o parcial direto no banco de dados.
+
+ O arquivo tem o mesmo cabeçalho do BD, seguido do número onde a varredura
+ começou, e de um único registro no formato do BD. Sempre é regravado por
+ inteiro, de forma atômica.
+*/
+
+use super::formato::{serializa_busca, le_registro, Cabecalho};
+use super::{Busca, ByteOrdem, caminho_de};
+use std::fs::{File, OpenOptions, rename, remove_file};
+use std::io::{self, Read, Write, BufReader};
+use std::path::{Path, PathBuf};
+
+const NOME_RASCUNHO: &str = "varredura_parcial.dat";
+
+fn caminho_rascunho() -> PathBuf
+   { caminho_de(NOME_RASCUNHO) }
+
+/* Grava o rascunho no caminho dado: primeiro num temporário, e só após
+ * forçá-lo no disco, o troca pelo antigo. */
+fn grava_em(caminho: &Path, inicio: u64, dados: &Busca) -> io::Result<()> {
+   let cabecalho = Cabecalho::novo(ByteOrdem::LittleEndian);
+   let temporario = caminho.with_extension("dat.novo");
+   let mut arquivo = {
+      OpenOptions::new()
+      .create(true).write(true).truncate(true)
+      .open(&temporario)?
+   };
+
+   arquivo.write_all(&cabecalho.serializa()[..])?;
+   arquivo.write_all(&inicio.to_le_bytes())?;
+   arquivo.write_all(&serializa_busca(dados, &cabecalho)[..])?;
+   arquivo.sync_all()?;
+   drop(arquivo);
+
+   rename(&temporario, caminho)
+}
+
+/* Lê o rascunho do caminho dado; um arquivo inexistente ou estragado é o
+ * mesmo que não ter rascunho algum. */
+fn le_de(caminho: &Path) -> Option<(u64, Busca)> {
+   let mut leitor = BufReader::new(File::open(caminho).ok()?);
+   let mut cabecalho = [0u8; 16];
+   let mut inicio = [0u8; 8];
+
+   leitor.read_exact(&mut cabecalho).ok()?;
+   let cabecalho = Cabecalho::deserializa(&cabecalho[..]).ok()?;
+   leitor.read_exact(&mut inicio).ok()?;
+   let dados = le_registro(&mut leitor, &cabecalho).ok()??;
+   Some((u64::from_le_bytes(inicio), dados))
+}
+
+/** Salva o parcial de uma varredura que começou em `inicio`. Feita para ser
+ passada como função de salvamento das buscas contínuas, por isso apenas
+ avisa quando não consegue gravar. */
+pub fn grava_varredura_parcial(inicio: u64, dados: &Busca) {
+   if let Err(erro) = grava_em(&caminho_rascunho(), inicio, dados)
+      { println!("não foi possível salvar o parcial da varredura: {}", erro); }
+}
+
+/** O parcial deixado por uma varredura interrompida, e onde esta havia
+ começado, se houver algum. */
+pub fn varredura_parcial() -> Option<(u64, Busca)>
+   { le_de(&caminho_rascunho()) }
+
+/// Apaga o rascunho, se existir.
+pub fn descarta_varredura_parcial()
+   { let _ = remove_file(caminho_rascunho()); }
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use std::env::temp_dir;
+   use std::fs::write;
+
+   #[test]
+   fn RascunhoIdaEVolta() {
+      let caminho = temp_dir().join("rascunho-ida-e-volta.dat");
+      let dados = (vec![101, 103, 107, 109, 113], 120, 7, 3);
+
+      grava_em(&caminho, 100, &dados).unwrap();
+      assert_eq!(le_de(&caminho), Some((100, dados)));
+      remove_file(caminho).unwrap();
+   }
+
+   #[test]
+   fn RascunhoEstragadoEIgnorado() {
+      let caminho = temp_dir().join("rascunho-estragado.dat");
+      let dados = (vec![2, 3, 5, 7], 10, 1, 1);
+
+      grava_em(&caminho, 0, &dados).unwrap();
+      let mut bytes = std::fs::read(&caminho).unwrap();
+      let ultimo = bytes.len() - 1;
+      bytes[ultimo] ^= 0xff;
+      write(&caminho, &bytes[..]).unwrap();
+
+      assert_eq!(le_de(&caminho), None);
+      assert_eq!(le_de(&temp_dir().join("rascunho-que-nao-existe")), None);
+      remove_file(caminho).unwrap();
+   }
+}
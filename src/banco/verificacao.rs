@@ -0,0 +1,359 @@
+/*! Verificação da integridade do banco de dados, no estilo de um "fsck".
+ Os registros são lidos um a um, e de cada um é conferido que os primos
+ estão em ordem estritamente crescente, que são mesmo primos(todos, ou uma
+ amostra deles), que não passam do último número verificado, e que o
+ registro começa logo depois de onde o anterior parou, sem sobreposição e
+ sem lacunas. O teste de primalidade é dado de fora(o Miller-Rabin do
+ motor), assim este módulo não depende dele.
+*/
+
+use super::formato::{abre_bd, le_registro, reescreve_bd};
+use super::indice_primos::reconstroi_indice;
+use super::ultima_insercao::{indice_gravado, atualiza_indice_de_insercao};
+use super::{Busca, carrega_bd, realiza_backup_bd};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufReader};
+
+/// Quantos primos de cada registro são testados.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rigor {
+   // apenas alguns, espalhados pelo registro, e as suas bordas.
+   Amostra(usize),
+   // todos, e também que não falta nenhum primo entre eles.
+   Completa
+}
+
+/// Um problema achado num registro(contados a partir do 1).
+#[derive(Debug, PartialEq)]
+pub enum Problema {
+   NaoPrimo { registro: u32, numero: u64 },
+   Duplicado { registro: u32, numero: u64 },
+   ForaDeOrdem { registro: u32, numero: u64 },
+   // primo que já estava na faixa coberta pelos registros anteriores.
+   Sobreposicao { registro: u32, numero: u64 },
+   /* o último primo do registro anterior, de novo no começo deste: a
+    * varredura antiga recomeçava no último número verificado. */
+   RepetidoNaBorda { registro: u32, numero: u64 },
+   // primo maior que o último número verificado do registro.
+   AlemDoVerificado { registro: u32, numero: u64 },
+   // o último número verificado é menor que o do registro anterior.
+   VerificadoRegride { registro: u32, anterior: u64, atual: u64 },
+   // há primo(s) estritamente entre 'de' e 'ate' fora do BD.
+   Lacuna { registro: u32, de: u64, ate: u64, primo: u64 },
+   // 'ultima_insercao.dat' não bate com a quantia de registros.
+   IndiceDeInsercao { gravado: Option<u32>, registros: u32 }
+}
+
+impl Display for Problema {
+   fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+      match *self {
+         Problema::NaoPrimo { registro, numero } =>
+            write!(f, "registro {}: {} não é primo", registro, numero),
+         Problema::Duplicado { registro, numero } =>
+            write!(f, "registro {}: {} está repetido", registro, numero),
+         Problema::ForaDeOrdem { registro, numero } =>
+            write!(f, "registro {}: {} está fora de ordem", registro, numero),
+         Problema::Sobreposicao { registro, numero } => write!(
+            f, "registro {}: {} já estava na faixa do registro anterior",
+            registro, numero
+         ),
+         Problema::RepetidoNaBorda { registro, numero } => write!(
+            f, "registro {}: {} repete o último primo do registro anterior",
+            registro, numero
+         ),
+         Problema::AlemDoVerificado { registro, numero } => write!(
+            f, "registro {}: {} passa do último número verificado",
+            registro, numero
+         ),
+         Problema::VerificadoRegride { registro, anterior, atual } => write!(
+            f, "registro {}: parou no {}, antes do anterior({})",
+            registro, atual, anterior
+         ),
+         Problema::Lacuna { registro, de, ate, primo } => write!(
+            f, "registro {}: lacuna entre {} e {}(o {} não está no BD)",
+            registro, de, ate, primo
+         ),
+         Problema::IndiceDeInsercao { gravado: Some(i), registros } => write!(
+            f, "índice de inserção diz {}, mas há {} registros",
+            i, registros
+         ),
+         Problema::IndiceDeInsercao { gravado: None, registros } => write!(
+            f, "índice de inserção ausente({} registros)", registros
+         )
+      }
+   }
+}
+
+/// O resultado de uma verificação.
+#[derive(Debug, Default)]
+pub struct Relatorio {
+   pub registros: u32,
+   pub primos: u64,
+   // quantos primos passaram pelo teste de primalidade.
+   pub testados: u64,
+   pub problemas: Vec<Problema>
+}
+
+/* Primeiro primo estritamente entre 'a' e 'b', se houver. */
+fn primo_entre(a: u64, b: u64, e_primo: fn(u64) -> bool) -> Option<u64> {
+   (a.saturating_add(1)..b).find(|&n| e_primo(n))
+}
+
+/* Posições, dentre 'n', testadas na amostra de 'k'. */
+fn amostra(n: usize, k: usize) -> Vec<usize> {
+   if n <= k
+      { return (0..n).collect(); }
+   if k <= 1
+      { return vec![0]; }
+   (0..k).map(|i| i * (n - 1) / (k - 1)).collect()
+}
+
+/** Confere os registros um a um, na ordem do BD, guardando o que já foi
+ visto dos anteriores. */
+pub struct Verificador {
+   rigor: Rigor,
+   e_primo: fn(u64) -> bool,
+   // último número verificado do registro anterior.
+   teto: u64,
+   // maior primo visto até aqui.
+   ultimo_primo: u64,
+   pub relatorio: Relatorio
+}
+
+impl Verificador {
+   pub fn novo(rigor: Rigor, e_primo: fn(u64) -> bool) -> Self {
+      Verificador {
+         rigor, e_primo, teto: 1, ultimo_primo: 0,
+         relatorio: Relatorio::default()
+      }
+   }
+
+   /** Confere o próximo registro. */
+   pub fn confere(&mut self, busca: &Busca) {
+      self.relatorio.registros += 1;
+      let registro = self.relatorio.registros;
+      let (primos, unv) = (&busca.0, busca.1);
+      let problemas = &mut self.relatorio.problemas;
+      let e_primo = self.e_primo;
+
+      if unv < self.teto {
+         let anterior = self.teto;
+         problemas.push(Problema::VerificadoRegride {
+            registro, anterior, atual: unv
+         });
+      }
+      // de onde este registro devia começar.
+      let inicio = self.teto.max(self.ultimo_primo);
+      // o último primo dos registros anteriores.
+      let borda = self.ultimo_primo;
+      let mut anterior = inicio;
+      let mut previo: Option<u64> = None;
+
+      for &numero in primos.iter() {
+         if previo == Some(numero)
+            { problemas.push(Problema::Duplicado { registro, numero }); }
+         else if previo.is_some_and(|p| numero < p)
+            { problemas.push(Problema::ForaDeOrdem { registro, numero }); }
+         else if numero == borda && previo.is_none()
+            { problemas.push(Problema::RepetidoNaBorda { registro, numero }); }
+         else if numero <= inicio
+            { problemas.push(Problema::Sobreposicao { registro, numero }); }
+         else {
+            let completa = self.rigor == Rigor::Completa;
+            // as bordas são sempre conferidas, o interior só na completa.
+            if completa || anterior == inicio {
+               if let Some(primo) = primo_entre(anterior, numero, e_primo) {
+                  problemas.push(Problema::Lacuna {
+                     registro, de: anterior, ate: numero, primo
+                  });
+               }
+            }
+            anterior = numero;
+         }
+         previo = Some(previo.map_or(numero, |p| p.max(numero)));
+         if numero > unv
+            { problemas.push(Problema::AlemDoVerificado { registro, numero }); }
+         self.ultimo_primo = self.ultimo_primo.max(numero);
+      }
+      // nada pode faltar entre o último primo e onde o registro parou.
+      if unv > anterior {
+         if let Some(primo) = primo_entre(anterior, unv + 1, e_primo) {
+            problemas.push(Problema::Lacuna {
+               registro, de: anterior, ate: unv + 1, primo
+            });
+         }
+      }
+
+      let posicoes = match self.rigor {
+         Rigor::Completa => amostra(primos.len(), primos.len()),
+         Rigor::Amostra(k) => amostra(primos.len(), k)
+      };
+      for i in posicoes {
+         let numero = primos[i];
+         if !e_primo(numero)
+            { problemas.push(Problema::NaoPrimo { registro, numero }); }
+         self.relatorio.testados += 1;
+      }
+      self.relatorio.primos += primos.len() as u64;
+      self.teto = self.teto.max(unv);
+   }
+}
+
+/** Verifica todo o banco de dados, e também se o índice de inserção bate
+ com a quantia de registros lidos. */
+pub fn verifica_bd(rigor: Rigor, e_primo: fn(u64) -> bool)
+  -> io::Result<Relatorio>
+{
+   let (arquivo, cabecalho) = abre_bd()?;
+   let mut leitor = BufReader::new(arquivo);
+   let mut verificador = Verificador::novo(rigor, e_primo);
+
+   while let Some(busca) = le_registro(&mut leitor, &cabecalho)?
+      { verificador.confere(&busca); }
+
+   let mut relatorio = verificador.relatorio;
+   let gravado = indice_gravado();
+   if gravado != Some(relatorio.registros) {
+      relatorio.problemas.push(Problema::IndiceDeInsercao {
+         gravado, registros: relatorio.registros
+      });
+   }
+   Ok(relatorio)
+}
+
+/** Conserta as buscas dadas: tira o que não é primo, o repetido, o que já
+ estava coberto por registros anteriores, e o que passa do último número
+ verificado; registros que ficam sem nada a acrescentar somem. Lacunas não
+ têm como ser consertadas aqui, só varrendo de novo. */
+pub fn repara(buscas: Vec<Busca>, e_primo: fn(u64) -> bool) -> Vec<Busca> {
+   let mut teto = 1;
+   let mut reparadas = Vec::with_capacity(buscas.len());
+
+   for (mut primos, unv, ttc, qb) in buscas {
+      primos.retain(|&p| p > teto && p <= unv && e_primo(p));
+      primos.sort_unstable();
+      primos.dedup();
+
+      if primos.is_empty() && unv <= teto
+         { continue; }
+      teto = teto.max(unv);
+      reparadas.push((primos, unv, ttc, qb));
+   }
+   reparadas
+}
+
+/** Conserta o banco de dados(ver `repara`), depois de guardar um backup
+ dele; refaz também o índice de inserção e o de primos. Retorna a quantia
+ de registros antes e depois. */
+pub fn repara_bd(e_primo: fn(u64) -> bool) -> io::Result<(usize, usize)> {
+   let (_, cabecalho) = abre_bd()?;
+   let bd = carrega_bd()?;
+   let total = bd.len();
+   let buscas: Vec<Busca> = {
+      (1..=total as u32)
+      .filter_map(|i| bd.get(&i).cloned())
+      .collect()
+   };
+
+   match realiza_backup_bd() {
+      Ok(caminho) =>
+         { println!("backup de antes do reparo em {}", caminho.display()); }
+      // sem um backup, nada é tocado.
+      Err(erro) => return Err(erro)
+   };
+   let reparadas = repara(buscas, e_primo);
+   reescreve_bd(&reparadas[..], cabecalho.ordem)?;
+   atualiza_indice_de_insercao(reparadas.len() as u32);
+   reconstroi_indice()?;
+   Ok((total, reparadas.len()))
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   // suficiente para os números pequenos dos testes.
+   fn e_primo(n: u64) -> bool
+      { n >= 2 && (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0) }
+
+   fn verifica(buscas: &[Busca], rigor: Rigor) -> Vec<Problema> {
+      let mut verificador = Verificador::novo(rigor, e_primo);
+      for busca in buscas
+         { verificador.confere(busca); }
+      verificador.relatorio.problemas
+   }
+
+   #[test]
+   fn BDIntegroNaoTemProblemas() {
+      let buscas = [
+         (vec![2, 3, 5, 7], 10, 1, 1),
+         (vec![11, 13], 16, 1, 1),
+         (vec![], 16, 1, 1),
+         (vec![17, 19, 23], 28, 1, 1)
+      ];
+      assert!(verifica(&buscas, Rigor::Completa).is_empty());
+      assert!(verifica(&buscas, Rigor::Amostra(1)).is_empty());
+   }
+
+   #[test]
+   fn AchaCadaTipoDeProblema() {
+      let buscas = [
+         // o 9 não é primo, e falta o 7.
+         (vec![2, 3, 5, 9], 10, 1, 1),
+         // o 7 é sobreposição, e o 13 repetido.
+         (vec![7, 11, 13, 13], 16, 1, 1),
+         // regride, e todos passam do último verificado.
+         (vec![17, 19, 23], 15, 1, 1),
+         // lacuna entre registros: o 29 e 31 faltam.
+         (vec![37], 40, 1, 1)
+      ];
+      let problemas = verifica(&buscas, Rigor::Completa);
+
+      assert_eq!(problemas, vec![
+         Problema::Lacuna { registro: 1, de: 5, ate: 9, primo: 7 },
+         Problema::NaoPrimo { registro: 1, numero: 9 },
+         Problema::Sobreposicao { registro: 2, numero: 7 },
+         Problema::Duplicado { registro: 2, numero: 13 },
+         Problema::VerificadoRegride { registro: 3, anterior: 16, atual: 15 },
+         Problema::AlemDoVerificado { registro: 3, numero: 17 },
+         Problema::AlemDoVerificado { registro: 3, numero: 19 },
+         Problema::AlemDoVerificado { registro: 3, numero: 23 },
+         Problema::Lacuna { registro: 4, de: 23, ate: 37, primo: 29 }
+      ]);
+      // na amostra, a lacuna do interior do registro passa batido.
+      let problemas = verifica(&buscas[..1], Rigor::Amostra(1));
+      assert!(problemas.is_empty());
+   }
+
+   #[test]
+   fn RepetidoNaBordaNaoESobreposicao() {
+      let buscas = [
+         (vec![2, 3, 5, 7], 7, 1, 1),
+         // o 7 de novo, como a varredura antiga fazia.
+         (vec![7, 11, 13], 16, 1, 1),
+         // já este 11 está bem no meio do que foi coberto.
+         (vec![11, 17], 18, 1, 1)
+      ];
+      assert_eq!(verifica(&buscas, Rigor::Completa), vec![
+         Problema::RepetidoNaBorda { registro: 2, numero: 7 },
+         Problema::Sobreposicao { registro: 3, numero: 11 }
+      ]);
+   }
+
+   #[test]
+   fn ReparoTiraOQueNaoPresta() {
+      let buscas = vec![
+         (vec![2, 3, 5, 9], 10, 1, 1),
+         (vec![13, 7, 11, 13], 16, 2, 2),
+         (vec![11, 13], 14, 3, 3),
+         (vec![17, 23], 20, 4, 4)
+      ];
+      assert_eq!(repara(buscas, e_primo), vec![
+         (vec![2, 3, 5], 10, 1, 1),
+         (vec![11, 13], 16, 2, 2),
+         (vec![17], 20, 4, 4)
+      ]);
+   }
+}
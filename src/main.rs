@@ -32,11 +32,54 @@ fn burla_prompt(args:&mut Vec<String>) -> bool {
    return false;
 }
 
+/* Retira a opção dada junto com o seu valor(--opcao V, ou --opcao=V), se
+ * houver. */
+fn opcao_com_valor(args:&mut Vec<String>, opcao: &str) -> Option<String> {
+   let com_igual = format!("{}=", opcao);
+   let indice = args.iter().position(|s| {
+      s == opcao || s.starts_with(com_igual.as_str())
+   })?;
+   let arg = args.remove(indice);
+
+   match arg.strip_prefix(com_igual.as_str()) {
+      Some(valor) => Some(valor.to_string()),
+      None if indice < args.len() => Some(args.remove(indice)),
+      None => None
+   }
+}
+
+/* Retira a quantia de processos pedida(--processos N), se houver. Um 
+ * valor inválido é só ignorado. */
+fn qtd_de_processos(args:&mut Vec<String>) -> Option<usize> {
+   let valor = opcao_com_valor(args, "--processos")?;
+
+   match valor.trim().parse::<usize>() {
+      Ok(qtd) if qtd > 0 => Some(qtd),
+      _ => { 
+         println!("quantia de processos inválida: '{}'", valor); 
+         None 
+      }
+   }
+}
+
 fn main() {
    // trabalhando na entrada de terminal...
    let mut entrada:Vec<String> = args().collect();
    // salva automaticamente pós-termino.
    let salvo_automatico = burla_prompt(&mut entrada);
+   // quantia de processos para as varreduras paralelas.
+   if let Some(qtd) = qtd_de_processos(&mut entrada)
+      { motor::define_qtd_de_processos(qtd); }
+   // onde ficam os dados, se for dado.
+   if let Some(dir) = opcao_com_valor(&mut entrada, "--dados")
+      { banco::define_diretorio(PathBuf::from(dir)); }
+   // como os primos são gravados, quando o BD é criado ou reescrito.
+   if let Some(nome) = opcao_com_valor(&mut entrada, "--codec") {
+      match banco::Codec::de_nome(nome.trim()) {
+         Some(codec) => banco::define_codec(codec),
+         None => println!("codificação inválida: '{}'", nome)
+      }
+   }
    /* obtem a opção e o possível argumento e 
     * gera o melhor enum que trabalha em cima
     * dele. */
@@ -19,9 +19,13 @@
 
 // biblioteca externa:
 extern crate utilitarios;
-use utilitarios::{tabelas::{Coluna, Tabela}, lanca_prompt, legivel::tempo};
+use utilitarios::{
+   tabelas::{Coluna, Tabela}, lanca_prompt, legivel::{self, tempo}
+};
 // Biblioteca do Rust:
-use std::fs::read_to_string;
+use std::fs::{read, read_to_string, File};
+use std::io::{self, BufWriter, ErrorKind};
+use std::ops::RangeInclusive;
 use std::time::Duration;
 use std::process::{Child, Command};
 use std::env::args;
@@ -31,15 +35,29 @@ use super::banco::*;
 #[doc(inline)]
 use super::motor::{ 
    busca_continua, busca_continua_temporizada, filtra_intervalo, 
-   divide_intervalo, gera_processo, simultaneadade, Primos
+   divide_intervalo, gera_processo, simultaneadade, Primos, e_primo_mr,
+   crivo_segmentado,
+   PontoDeSalvamento, junta_varreduras
 };
 use super::{Dados, A_BUSCAR};
 use crate::computa_caminho;
 // Extensão do módulo.
 mod tipo;
-pub use tipo::{Argumentos, Funcao, transforma};
+pub use tipo::{Argumentos, AcaoBackup, Funcao, ModoBD, transforma};
 mod stream_serializado;
 pub use stream_serializado::{despeja_bytes, colhe_resultado};
+mod importacao;
+use importacao::{le_numeros, confere_lista};
+mod estatisticas;
+use estatisticas::{calcula, erro_relativo, Li, Real};
+
+// De quanto em quanto as varreduras longas salvam o que já acharam.
+const SALVAMENTO_PERIODO: Duration = Duration::from_secs(30);
+const SALVAMENTO_QTD: usize = 10_000;
+// A partir de qual tamanho uma faixa do 'preenche' é varrida em paralelo.
+const FAIXA_GRANDE: u64 = 5_000_000;
+// Quantos primos, de cada registro, o 'verifica-bd' testa por padrão.
+const AMOSTRA: usize = 32;
 
 
 /**
@@ -60,21 +78,33 @@ pub fn menu(argumento:Argumentos, pula_confirmacao:bool) {
       Argumentos::Procura => {
          // varre por uma quantia de primos.
          // obtem-se o número onde parou a última varredura.
-         let ultimo = ultimo_numero_computado().unwrap();
-         // busca a quantidade e obtem-se eles e seus metadados produzidos.
-         let dados = busca_continua(ultimo, A_BUSCAR);
+         let dados = varredura_com_salvamentos(
+            pula_confirmacao,
+            // busca a quantidade e obtem-se eles e seus metadados.
+            |ultimo, feito, ponto| {
+               let falta = A_BUSCAR.saturating_sub(feito.0.len() as u64);
+               busca_continua(ultimo, falta, Some(ponto))
+            }
+         );
          // guarda tais dados no banco de dados.
          salvar_varredura(dados, salva_no_bd);
       } Argumentos::ProcuraTempo(t) => { 
          // varre por primos dado um determinado tempo.
          let t: u64 = t as u64;
          println!("Seu tempo demandado: {}", tempo(t, false));
-         // carrega de onde parou:
-         let onde_parou = ultimo_numero_computado().unwrap();
          // cria "intervalo" de computação.
          let intervalo = Duration::from_secs(t);
-         // começa... e já 'captura' dados da consulta...
-         let dados = busca_continua_temporizada(onde_parou, intervalo);
+         // começa de onde parou... e já 'captura' dados da consulta...
+         let dados = varredura_com_salvamentos(
+            pula_confirmacao,
+            |onde_parou, feito, ponto| {
+               let falta = {
+                  intervalo
+                  .saturating_sub(Duration::from_secs(feito.2))
+               };
+               busca_continua_temporizada(onde_parou, falta, Some(ponto))
+            }
+         );
          // informação do termino.
          envia_notificao(&dados);
          // confirmando se deseja armazenar os dados:
@@ -87,10 +117,14 @@ pub fn menu(argumento:Argumentos, pula_confirmacao:bool) {
             { salvar_varredura(dados, salva_no_bd); }
       } Argumentos::ProcuraQtd(q) => {
          println!("sua quantia demandada: {} primos",q);
-         // último número verificado.
-         let unv = ultimo_numero_computado().unwrap();
-         // dados conseguidos na busca.
-         let dados = busca_continua(unv, q);
+         // dados conseguidos na busca, a partir do último verificado.
+         let dados = varredura_com_salvamentos(
+            pula_confirmacao,
+            |unv, feito, ponto| {
+               let falta = q.saturating_sub(feito.0.len() as u64);
+               busca_continua(unv, falta, Some(ponto))
+            }
+         );
          // guarda no banco de dados a varredura.
          if pula_confirmacao {
             // salva automaticamente.
@@ -98,18 +132,429 @@ pub fn menu(argumento:Argumentos, pula_confirmacao:bool) {
          }
          else 
             { salvar_varredura(dados, salva_no_bd); }
-      } Argumentos::Backup => {
-         println!(
-            concat!(
-               "copiando dados do banco de dados de",
-               "texto para o binário."
-            )
-         );
-         // antes de começar faz a cópia do antigo.
-         realiza_backup_bd(); 
+      } Argumentos::Backup(AcaoBackup::Lista) => 
+         { lista_com_retencao(); }
+      Argumentos::Backup(AcaoBackup::Poda { simulado }) => {
+         match poda_backups(&RETENCAO_PADRAO, simulado) {
+            Ok(podados) => {
+               let verbo = if simulado { "seria podado" } else { "podado" };
+               for b in podados.iter() 
+                  { println!("{}: '{}'", verbo, b.nome); }
+               println!("{} backup(s) no total.", podados.len());
+            } Err(erro) => 
+               { println!("a poda falhou: {}", erro); }
+         };
+      } Argumentos::Backup(AcaoBackup::Realiza) => {
+         println!("fazendo o backup do banco de dados...");
+         // um backup igual ao BD de agora é só reaproveitado.
+         match realiza_backup_bd() {
+            Ok(caminho) => 
+               { println!("backup salvo em {}", caminho.display()); }
+            Err(erro) => 
+               { println!("o backup falhou: {}", erro); }
+         };
+      } Argumentos::Restaura(escolha) => 
+         { restaura(escolha, pula_confirmacao); }
+      Argumentos::VerificaBD { completa, reparar } => 
+         { verificacao_do_bd(completa, reparar); }
+      Argumentos::Preenche => 
+         { preenche(pula_confirmacao); }
+      Argumentos::Compacta(limite) => {
+         let limite = limite.unwrap_or(PRIMOS_POR_REGISTRO);
+         println!("compactando, até {} primos por registro...", limite);
+         match compacta_bd(limite, e_primo_mr) {
+            Ok((antes, depois)) if antes == depois => 
+               { println!("nenhum registro a fundir({} registros).", antes); }
+            Ok((antes, depois)) => 
+               { println!("de {} para {} registros.", antes, depois); }
+            Err(erro) => 
+               { println!("a compactação falhou: {}", erro); }
+         };
+      }
+      Argumentos::Converte(ModoBD::Roda) => {
+         // o tamanho só é lido depois da conversão.
+         match (converte_para_roda(), tamanho_roda()) {
+            (Ok((na_roda, na_lista)), tamanho) => println!(
+               "{} registros no mapa de bits, {} na lista; {} ao todo.",
+               na_roda, na_lista, 
+               tamanho.map_or("?".into(), |t| legivel::tamanho(t, true))
+            ),
+            (Err(erro), _) => 
+               { println!("a conversão para a roda falhou: {}", erro); }
+         };
+      }
+      Argumentos::Converte(ModoBD::Lista) => {
+         match converte_para_lista() {
+            Ok(total) => 
+               { println!("de volta à lista, com {} registros.", total); }
+            Err(erro) => 
+               { println!("a conversão para a lista falhou: {}", erro); }
+         };
+      }
+      Argumentos::Exporta { formato, registros, de, ate, saida } => 
+         { exporta(formato, registros, de..=ate, saida); }
+      Argumentos::Importa(arquivo) => 
+         { importa(Path::new(&arquivo), pula_confirmacao); }
+      Argumentos::Estatisticas => 
+         { estatisticas_do_bd(); }
+      Argumentos::Enesimo(k) => {
+         match n_esimo(k) {
+            Ok(Some(primo)) => 
+               { println!("o {}º primo guardado é o {}.", k, primo); }
+            Ok(None) => 
+               { println!("o banco de dados não tem {} primos.", k); }
+            Err(erro) => 
+               { println!("a consulta falhou: {}", erro); }
+         };
+      }
+      Argumentos::Entre(a, b) => {
+         match primos_entre(a, b) {
+            Ok(primos) => {
+               for p in primos.iter()
+                  { println!("{}", p); }
+               println!("{} primos guardados entre {} e {}.", primos.len(), a, b);
+            } Err(erro) => 
+               { println!("a consulta falhou: {}", erro); }
+         };
+      }
+      Argumentos::Verifica(n) => {
+         if e_primo_mr(n) { 
+            println!("{} é primo.", n); 
+            // consulta direta via índice, sem carregar todo o BD.
+            if let Ok(true) = contem(n)
+               { println!("e já está guardado no banco de dados."); }
+         } else
+            { println!("{} não é primo.", n); }
       } Argumentos::Privado(tipo) => 
-         { forques_demanados(tipo); }
+         { forques_demanados(tipo, pula_confirmacao); }
+   }
+}
+
+/* Lista os backups, com tamanho e idade, e o que a retenção faria com
+ * cada um deles. */
+fn lista_com_retencao() {
+   let backups = match classifica_backups(&RETENCAO_PADRAO) {
+      Ok(backups) => backups,
+      Err(erro) => 
+         { println!("não foi possível listar os backups: {}", erro); return; }
+   };
+   if backups.is_empty() 
+      { println!("não há nenhum backup."); return; }
+   let mut total = 0;
+
+   for (b, fica) in backups.iter() {
+      let destino = if *fica { "mantido" } else { "seria podado" };
+      let legado = if b.legado { ", zip antigo" } else { "" };
+      total += b.tamanho;
+      println!(
+         "{:>8} ==> '{}' ({}, há {}{}) {}", b.rotulo, b.nome,
+         legivel::tamanho(b.tamanho, true),
+         tempo(b.idade.as_secs(), true), legado, destino
+      );
+   }
+   println!(
+      "{} backups, somando {}.", 
+      backups.len(), legivel::tamanho(total, true)
+   );
+}
+
+/* Verifica a integridade do BD, imprimindo o relatório; e, se pedido e
+ * houver problemas, conserta o BD. */
+fn verificacao_do_bd(completa: bool, reparar: bool) {
+   let rigor = if completa { Rigor::Completa } else { Rigor::Amostra(AMOSTRA) };
+   println!("verificando o banco de dados({:?})...", rigor);
+
+   let relatorio = match verifica_bd(rigor, e_primo_mr) {
+      Ok(relatorio) => relatorio,
+      Err(erro) => 
+         { println!("não foi possível ler o BD: {}", erro); return; }
+   };
+   for problema in relatorio.problemas.iter()
+      { println!("\t{}", problema); }
+   println!(
+      "{} registros, {} primos({} testados), {} problemas.",
+      relatorio.registros, relatorio.primos, relatorio.testados,
+      relatorio.problemas.len()
+   );
+
+   if relatorio.problemas.is_empty() || !reparar
+      { return; }
+   match repara_bd(e_primo_mr) {
+      Ok((antes, depois)) => {
+         println!("BD reparado, de {} para {} registros.", antes, depois);
+         println!("lacunas, se houver, só varrendo de novo aquelas faixas.");
+      } Err(erro) => 
+         { println!("o reparo falhou: {}", erro); }
+   };
+}
+
+/* Varre as faixas que nenhum registro do BD cobre, e insere o que for
+ * achado, cada faixa no seu lugar, no meio do BD. */
+fn preenche(pula_confirmacao: bool) {
+   let cobertas = match faixas_cobertas() {
+      Ok(cobertas) => cobertas,
+      Err(erro) => 
+         { println!("não foi possível ler o BD: {}", erro); return; }
+   };
+   let repetidas = faixas_repetidas(&cobertas[..]);
+   let descobertas = faixas_descobertas(&cobertas[..], e_primo_mr);
+
+   if !repetidas.is_empty() {
+      println!(
+         "{} faixa(s) varrida(s) mais de uma vez, a primeira é {:?}; \
+         veja o 'verifica-bd --reparar'.", repetidas.len(), repetidas[0]
+      );
    }
+   let total: u64 = descobertas.iter().map(|f| f.end() - f.start() + 1).sum();
+   println!(
+      "{} faixa(s) descoberta(s), somando {} números.", 
+      descobertas.len(), total
+   );
+
+   let mut novas: Vec<Dados> = Vec::new();
+   for faixa in descobertas {
+      let (inicio, fim) = (*faixa.start(), *faixa.end());
+      let cronometro = Instant::now();
+      let varredura = {
+         if fim - inicio >= FAIXA_GRANDE 
+            { varre_em_paralelo(faixa) }
+         else 
+            { Ok(crivo_segmentado(inicio, fim)) }
+      };
+      let primos = match varredura {
+         Ok(primos) => primos,
+         Err(erro) => {
+            // o que já foi achado, nas faixas anteriores, ainda vale.
+            println!("a varredura de {}..={} falhou: {}", inicio, fim, erro);
+            break;
+         }
+      };
+
+      if primos.is_empty()
+         { continue; }
+      println!("{}..={}: {} primos faltavam.", inicio, fim, primos.len());
+      let buscas = (fim - inicio) / 100 + 1;
+      novas.push((primos, fim, cronometro.elapsed().as_secs(), buscas));
+   }
+
+   if novas.is_empty() 
+      { println!("nada faltava no BD."); return; }
+   if !pula_confirmacao {
+      let pergunta = format!("Inserir os {} novos registros[s/n]", novas.len());
+      if lanca_prompt(pergunta.as_str()).trim() != "s"
+         { println!("nada foi inserido."); return; }
+   }
+   match insere_em_ordem(novas) {
+      Ok(total) => 
+         { println!("inseridos, o BD tem agora {} registros.", total); }
+      Err(erro) => 
+         { println!("a inserção falhou: {}", erro); }
+   };
+}
+
+/* Restaura o BD do backup escolhido. Sem escolha, lista os backups e
+ * pergunta qual deles; uma resposta vazia cancela tudo. */
+fn restaura(escolha: Option<String>, pula_confirmacao: bool) {
+   let escolha = match escolha {
+      Some(escolha) => escolha,
+      None => {
+         let backups = match lista_backups() {
+            Ok(backups) => backups,
+            Err(erro) => 
+               { println!("não foi possível listar os backups: {}", erro); return; }
+         };
+         if backups.is_empty() 
+            { println!("não há nenhum backup."); return; }
+         for b in backups.iter() {
+            println!(
+               "{:>8} ==> '{}' ({})", b.rotulo, b.nome, 
+               legivel::tamanho(b.tamanho, true)
+            );
+         }
+         lanca_prompt("Qual backup restaurar[vazio cancela]")
+      }
+   };
+   let escolha = escolha.trim();
+
+   if escolha.is_empty() 
+      { println!("nada foi restaurado."); return; }
+   let caminho = match escolhe_backup(escolha) {
+      Ok(Some(caminho)) => caminho,
+      Ok(None) => 
+         { println!("nenhum backup '{}' achado.", escolha); return; }
+      Err(erro) => 
+         { println!("não foi possível achar o backup: {}", erro); return; }
+   };
+   if !pula_confirmacao {
+      let pergunta = format!(
+         "Trocar o BD atual por '{}'[s/n]", 
+         caminho.display()
+      );
+      if lanca_prompt(pergunta.as_str()).trim() != "s"
+         { println!("nada foi restaurado."); return; }
+   }
+
+   match restaura_backup(&caminho) {
+      Ok(anterior) => {
+         if let Some(anterior) = anterior 
+            { println!("o BD anterior foi salvo em {}", anterior.display()); }
+         println!("BD restaurado de {}", caminho.display());
+      } Err(erro) => 
+         { println!("a restauração falhou, nada foi trocado: {}", erro); }
+   };
+}
+
+/* Exporta o BD para o arquivo dado, ou para a saída padrão. Neste caso,
+ * as mensagens vão para a saída de erros, para não se misturar com os
+ * dados, e um 'pipe' fechado no meio(um 'head', por exemplo) não é erro. */
+fn exporta(formato: FormatoExportacao, registros: bool, 
+  faixa: RangeInclusive<u64>, saida: Option<String>) 
+{
+   let resultado = match saida.as_ref() {
+      Some(caminho) => {
+         File::create(caminho)
+         .map(BufWriter::new)
+         .and_then(|mut arquivo| {
+            exporta_bd(&mut arquivo, formato, faixa, registros)
+         })
+      } None => {
+         let mut padrao = BufWriter::new(io::stdout().lock());
+         exporta_bd(&mut padrao, formato, faixa, registros)
+      }
+   };
+   let o_que = if registros { "registros" } else { "primos" };
+
+   match (resultado, saida) {
+      (Ok(total), Some(caminho)) => 
+         { println!("{} {} exportados para '{}'.", total, o_que, caminho); }
+      (Ok(_), None) => (),
+      (Err(ref erro), None) if erro.kind() == ErrorKind::BrokenPipe => (),
+      (Err(erro), Some(_)) => 
+         { println!("a exportação falhou: {}", erro); }
+      (Err(erro), None) => 
+         { eprintln!("a exportação falhou: {}", erro); }
+   };
+}
+
+/* Grava no BD os primos do arquivo dado, depois de conferir cada um, e de
+ * que a faixa deles não tem nada que o BD já cubra. Vão como um novo
+ * registro, no fim do BD, ou no buraco onde couberem. */
+fn importa(arquivo: &Path, pula_confirmacao: bool) {
+   let numeros = match read(arquivo).and_then(le_numeros) {
+      Ok(numeros) => numeros,
+      Err(erro) => { 
+         println!("não foi possível ler '{}': {}", arquivo.display(), erro); 
+         return; 
+      }
+   };
+   let (inicio, fim) = match (numeros.first(), numeros.last()) {
+      (Some(&inicio), Some(&fim)) => (inicio, fim),
+      _ => { println!("nenhum número em '{}'.", arquivo.display()); return; }
+   };
+   println!("conferindo {} números, de {} a {}...", numeros.len(), inicio, fim);
+   if let Err(erro) = confere_lista(&numeros[..])
+      { println!("a lista foi rejeitada: {}", erro); return; }
+
+   let cobertas = match faixas_cobertas() {
+      Ok(cobertas) => cobertas,
+      // ainda sem BD, nada é coberto.
+      Err(ref erro) if erro.kind() == ErrorKind::NotFound => Vec::new(),
+      Err(erro) => 
+         { println!("não foi possível ler o BD: {}", erro); return; }
+   };
+   let faixa = inicio..=fim;
+   let sobreposta = cobertas.iter().find(|f| {
+      f.start() <= faixa.end() && faixa.start() <= f.end()
+   });
+   if let Some(coberta) = sobreposta {
+      println!("a faixa {:?} já é coberta pelo BD em {:?}.", faixa, coberta);
+      return;
+   }
+   if !pula_confirmacao {
+      let pergunta = format!("Gravar os {} primos no BD[s/n]", numeros.len());
+      if lanca_prompt(pergunta.as_str()).trim() != "s"
+         { println!("nada foi gravado."); return; }
+   }
+
+   /* nada foi varrido aqui, então não há tempo nem buscas a registrar; o
+    * último verificado é o último primo da lista. */
+   let busca = (numeros, fim, 0, 0);
+   // antes do fim do BD, tem que ir no meio, para ficar em ordem.
+   if cobertas.iter().any(|f| *f.end() > fim) {
+      match insere_em_ordem(vec![busca]) {
+         Ok(total) => 
+            { println!("inseridos, o BD tem agora {} registros.", total); }
+         Err(erro) => 
+            { println!("a inserção falhou: {}", erro); }
+      };
+   } else {
+      salva_no_bd(busca);
+      println!("gravados no fim do BD.");
+   }
+}
+
+/* Se uma varredura anterior foi interrompida, pergunta o que fazer com o
+ * que ela deixou salvo: retomar de onde parou, gravar no BD como está, ou
+ * descartar. Retorna o ínicio e os dados dela, caso for retomada. */
+fn varredura_interrompida(pula_confirmacao: bool) -> Option<(u64, Dados)> {
+   let (inicio, parcial) = varredura_parcial()?;
+
+   // o BD já andou desde então, o rascunho não serve mais.
+   if Some(inicio) != ultimo_numero_computado() {
+      descarta_varredura_parcial();
+      return None;
+   }
+   println!(
+      "há uma varredura interrompida com {} primos, de {} até {}.",
+      parcial.0.len(), inicio, parcial.1
+   );
+   let resposta = {
+      if pula_confirmacao 
+         { String::from("gravar") }
+      else
+         { lanca_prompt("O que fazer com ela[retomar/gravar/descartar]") }
+   };
+
+   match resposta.trim() {
+      "gravar" => {
+         salva_no_bd(parcial);
+         descarta_varredura_parcial();
+         None
+      } "descartar" => 
+         { descarta_varredura_parcial(); None }
+      // na dúvida, nada do que foi feito é perdido.
+      _ => Some((inicio, parcial))
+   }
+}
+
+/* Executa a 'busca' dada com salvamentos periódicos, começando do último 
+ * número verificado, ou retomando uma varredura interrompida. A 'busca' 
+ * recebe de onde começar, o que já foi feito(para descontar da demanda),
+ * e o ponto de salvamento. O retorno já inclui o que foi retomado. */
+fn varredura_com_salvamentos<F>(pula_confirmacao: bool, busca: F) -> Dados
+  where F: FnOnce(u64, &Dados, &mut PontoDeSalvamento) -> Dados
+{
+   let mut ponto = PontoDeSalvamento::novo(
+      SALVAMENTO_PERIODO, SALVAMENTO_QTD, 
+      grava_varredura_parcial
+   );
+
+   let dados = match varredura_interrompida(pula_confirmacao) {
+      Some((inicio, parcial)) => {
+         println!("retomando a partir do {}...", parcial.1);
+         let feito = parcial.clone();
+         ponto.anterior = Some((inicio, parcial));
+         let continuacao = busca(feito.1, &feito, &mut ponto);
+         junta_varreduras(&feito, &continuacao)
+      } None => {
+         let ultimo = ultimo_numero_computado().unwrap();
+         busca(ultimo, &(Vec::new(), ultimo, 0, 0), &mut ponto)
+      }
+   };
+   /* terminada a varredura, o rascunho não é mais necessário, o resultado
+    * agora é gravado(ou recusado) por inteiro. */
+   descarta_varredura_parcial();
+   dados
 }
 
 // Entrada para confirmação, retorna verdadeiro ou falso.
@@ -158,38 +603,59 @@ fn informacao_da_varredura(dados:&Dados) {
 }
 
 pub fn info_bd_binario() {
-   //let unv = ultimo_numero_computado().unwrap();
-   let primos_encontrados = coleta_todos_primos().unwrap();
-   let qtd_primos:u64 = primos_encontrados.len() as u64;
+   /* uma passada só pelos registros, para a contagem, e outra pelos 
+    * primos, para as amostras; o BD nunca fica todo na memória. */
+   let mut qtd_primos: u64 = 0;
+   let mut unv: Option<u64> = None;
+   for busca in iter_buscas() {
+      match busca {
+         Ok((primos, ultimo, ..)) => {
+            qtd_primos += primos.len() as u64;
+            unv = Some(ultimo);
+         } Err(erro) => {
+            println!("não foi possível ler o banco de dados: {}", erro);
+            return;
+         }
+      }
+   }
+   let unv = match unv {
+      Some(unv) => unv,
+      None => 
+         { println!("o banco de dados está vazio."); return; }
+   };
    println!(
       "
       \rquantidade de primos:\t{}
       \rúltima verificação:\t{}
-      ", qtd_primos,
-      ultimo_numero_computado().unwrap() 
+      ", qtd_primos, unv
    );
 
-   // primeiros primos. 
-   let inicio = Coluna::nova(
-      "inicio",
-      primos_encontrados[0..30]
-      .to_vec()
-   );
-   // primos do centro da distribuição encontrada.
-   let meio = (qtd_primos / 2) as usize;
-   let centro = Coluna::nova(
-      "meio",
-      primos_encontrados[meio-15..meio+15]
-      .to_vec()
-   );
-   // limites:
+   // posições das amostras: início, centro e fim da distribuição.
    let f = qtd_primos as usize;
-   // últimos primos.
-   let fim = Coluna::nova(
-      "fim",
-      primos_encontrados[f-30..f]
-      .to_vec()
-   );
+   let meio = f / 2;
+   let faixas = [
+      0..30.min(f), 
+      meio.saturating_sub(15)..(meio + 15).min(f),
+      f.saturating_sub(30)..f
+   ];
+   let mut amostras: [Primos; 3] = Default::default();
+   for (i, primo) in iter_primos().enumerate() {
+      let primo = match primo {
+         Ok(primo) => primo,
+         Err(erro) => {
+            println!("não foi possível ler o banco de dados: {}", erro);
+            return;
+         }
+      };
+      for (faixa, amostra) in faixas.iter().zip(amostras.iter_mut()) {
+         if faixa.contains(&i)
+            { amostra.push(primo); }
+      }
+   }
+   let [inicio, centro, fim] = amostras;
+   let inicio = Coluna::nova("inicio", inicio);
+   let centro = Coluna::nova("meio", centro);
+   let fim = Coluna::nova("fim", fim);
    // visualização mais estruturada.
    println!("amostra do produzido até o momento:");
 
@@ -200,6 +666,109 @@ pub fn info_bd_binario() {
    println!("{}", tabela);
 }
 
+/* Um real para as tabelas, com as casas e o sufixo dados. */
+fn real(valor: f64, casas: usize, sufixo: &'static str) -> Real
+   { Real { valor, casas, sufixo } }
+
+/* A contagem dos primos do BD(π(x)) em cada potência de dez, contra as
+ * aproximações x/ln x e Li(x); a densidade por década; e as classes de 
+ * resto módulo 4 e 3. Uma passada só pelos primos, que não ficam na 
+ * memória. */
+pub fn estatisticas_do_bd() {
+   use utilitarios::tabela_visualizacao::{Coluna, Tabela};
+
+   let ultimo = match ultimo_numero_computado() {
+      Some(ultimo) => ultimo,
+      None => { println!("o banco de dados está vazio."); return; }
+   };
+   // com um buraco no meio, π(x) sairia menor, sem nenhum aviso.
+   let limite = match prefixo_coberto(e_primo_mr) {
+      Ok(Some(limite)) => limite,
+      Ok(None) => { 
+         println!("o BD não cobre os números desde o dois; veja o 'preenche'."); 
+         return; 
+      } Err(erro) => { 
+         println!("não foi possível ler o banco de dados: {}", erro); 
+         return; 
+      }
+   };
+   if limite < ultimo {
+      println!(
+         "o BD tem buracos, ou registros fora de ordem, depois do {}; as \
+         estatísticas param ali(veja o 'verifica-bd' e o 'preenche').", 
+         limite
+      );
+   }
+   let (marcos, decadas) = match calcula(iter_primos(), limite) {
+      Ok(resultado) => resultado,
+      Err(erro) => { 
+         println!("não foi possível ler o banco de dados: {}", erro); 
+         return; 
+      }
+   };
+   // nem uma década inteira, não há o que comparar.
+   if marcos.is_empty() || marcos[0].pi == 0
+      { println!("poucos primos para as estatísticas."); return; }
+   // são poucas linhas, não há o que dividir em painéis.
+   println!("contagem dos primos até x, de 2 a {}:", limite);
+   let x: Vec<u64> = marcos.iter().map(|m| m.x).collect();
+   let pi: Vec<u64> = marcos.iter().map(|m| m.pi).collect();
+   let (mut x_ln, mut erro_ln) = (Vec::new(), Vec::new());
+   let (mut li, mut erro_li) = (Vec::new(), Vec::new());
+
+   for m in marcos.iter() {
+      let aproximacao = m.x as f64 / (m.x as f64).ln();
+      x_ln.push(real(aproximacao, 1, ""));
+      erro_ln.push(real(erro_relativo(aproximacao, m.pi), 3, "%"));
+      li.push(real(Li(m.x as f64), 1, ""));
+      erro_li.push(real(erro_relativo(Li(m.x as f64), m.pi), 3, "%"));
+   }
+   /* os rótulos têm ao menos quatro letras: a tabela não sabe desenhar
+    * colunas mais estreitas, o que acontece num BD pequeno. */
+   let mut tabela = Tabela::nova(false);
+   tabela.adiciona(Coluna::nova("até x", x.clone()));
+   tabela.adiciona(Coluna::nova("pi(x)", pi));
+   tabela.adiciona(Coluna::nova("x/ln x", x_ln));
+   tabela.adiciona(Coluna::nova("erro", erro_ln));
+   tabela.adiciona(Coluna::nova("Li(x)", li));
+   tabela.adiciona(Coluna::nova("erro Li", erro_li));
+   println!("{}", tabela);
+
+   // a esperada, pelo Li, é a integral de 1/ln t sobre a década.
+   println!("densidade dos primos por década:");
+   let inicio: Vec<u64> = decadas.iter().map(|d| d.inicio).collect();
+   let fim: Vec<u64> = decadas.iter().map(|d| d.fim).collect();
+   let qtd: Vec<u64> = decadas.iter().map(|d| d.qtd).collect();
+   let densidade: Vec<Real> = {
+      decadas.iter()
+      .map(|d| real(100.0 * d.densidade(), 4, "%"))
+      .collect()
+   };
+   let esperada: Vec<Real> = decadas.iter().map(|d| {
+      let (a, b) = ((d.inicio as f64).max(2.0), d.fim as f64 + 1.0);
+      real(Li(b) - Li(a), 1, "")
+   }).collect();
+   let mut tabela = Tabela::nova(false);
+   tabela.adiciona(Coluna::nova("início", inicio));
+   tabela.adiciona(Coluna::nova("final", fim));
+   tabela.adiciona(Coluna::nova("primos", qtd));
+   tabela.adiciona(Coluna::nova("densidade", densidade));
+   tabela.adiciona(Coluna::nova("pelo Li", esperada));
+   println!("{}", tabela);
+
+   // o viés de Chebyshev: 4k+3 e 3k+2 quase sempre à frente.
+   println!("primos por classe de resto, até x:");
+   let classe = |f: fn(&estatisticas::Marco) -> u64| -> Vec<u64> 
+      { marcos.iter().map(f).collect() };
+   let mut tabela = Tabela::nova(false);
+   tabela.adiciona(Coluna::nova("até x", x));
+   tabela.adiciona(Coluna::nova("4k+1", classe(|m| m.mod4[0])));
+   tabela.adiciona(Coluna::nova("4k+3", classe(|m| m.mod4[1])));
+   tabela.adiciona(Coluna::nova("3k+1", classe(|m| m.mod3[0])));
+   tabela.adiciona(Coluna::nova("3k+2", classe(|m| m.mod3[1])));
+   println!("{}", tabela);
+}
+
 use utilitarios::legivel::{valor_legivel};
 
 // notificação de termino da mineração.
@@ -219,7 +788,59 @@ fn envia_notificao(dados:&Dados) {
 use crate::banco::deleta_caminho;
 use std::str::FromStr;
 use std::path::Path;
-use super::motor::{varre};
+use super::motor::{
+   varre_em_paralelo, qtd_de_processos, junta_ordenados, Intervalo
+};
+use std::time::Instant;
+
+/* Reparte o intervalo entre vários subprocessos, junta(em ordem) o que 
+ * cada um achou, e oferece o resultado para ser gravado como uma varredura
+ * comum. Só é gravado se o intervalo começar onde o BD parou, do contrário
+ * os primos ficariam repetidos, ou com um buraco. */
+fn ignicao(a: Intervalo, qtd_sp: usize, pula_confirmacao: bool) {
+   let (inicio, fim) = (*a.start(), *a.end());
+   let cronometro = Instant::now();
+   let mut geral = divide_intervalo(a, qtd_sp);
+   let mut forques: Vec<Child>;
+   forques = Vec::with_capacity(qtd_sp + 3);
+
+   for (o, i) in geral.drain(..).enumerate() {
+      println!("{}º. {:#?}", (o+1), i);
+      let processo = gera_processo(i).unwrap(); 
+      forques.push(processo); 
+   }
+   // cada subprocesso devolve a sua parte em ordem.
+   let partes: Vec<Primos> = {
+      forques.iter_mut()
+      .map(colhe_resultado)
+      .collect()
+   };
+   let mut resultados = junta_ordenados(partes);
+   println!("primos encontrados: {}", resultados.len());
+
+   // metadados como os de uma varredura comum(buscas de cem em cem).
+   let buscas = (fim - inicio) / 100 + 1;
+   let tempo = cronometro.elapsed().as_secs();
+
+   match ultimo_numero_computado() {
+      Some(ultimo) if inicio == ultimo || inicio == ultimo + 1 => {
+         /* o último verificado, se for primo, já está no BD(a varredura
+          * costuma parar num primo); não pode ir de novo. */
+         resultados.retain(|&p| p > ultimo);
+         let dados = (resultados, fim, tempo, buscas);
+         if pula_confirmacao 
+            { salva_no_bd(dados); }
+         else 
+            { salvar_varredura(dados, salva_no_bd); }
+      } Some(ultimo) => {
+         println!(
+            "o BD parou em {}, e o intervalo começa em {}; não será gravado.",
+            ultimo, inicio
+         );
+      } None => 
+         { println!("não foi possível ler o BD; nada será gravado."); }
+   };
+}
 /* Trazendo para cá, já que, um aninhamento naquele nível fica muito 
  * confuso para se mexer. Aqui cuida de chamadas privadas, um modo de dá 
  * 'fork' no programa para funções internas do programa. Os comandos que 
@@ -229,34 +850,18 @@ use super::motor::{varre};
  *    ignção a..=b(intervalo)
  *    inverte-última-inserção
  */
-fn forques_demanados(tipo: Funcao) {
+fn forques_demanados(tipo: Funcao, pula_confirmacao: bool) {
    const QTD_THREADS: usize = 10;
    // total de forks à "chocar".
    //const QTD_SP: usize = 4;
-   let qtd_sp = num_cpus::get();
+   let qtd_sp = qtd_de_processos();
    // pulando nome do programa...
    let mut argumentos = args().skip(1);
 
    match tipo {
       Funcao::Chamada => {
          let a = filtra_intervalo(argumentos);
-         let mut geral = divide_intervalo(a, qtd_sp);
-         let mut forques: Vec<Child>;
-         forques = Vec::with_capacity(2 * qtd_sp);
-         for (o, i) in geral.drain(..).enumerate() {
-            println!("{}º. {:#?}", (o+1), i);
-            let processo = gera_processo(i).unwrap(); 
-            forques.push(processo); 
-         }
-         let mut resultados = Primos::with_capacity(30_000);
-         for sp in forques.iter_mut() { 
-            let r = colhe_resultado(sp);
-            resultados.extend(r); 
-         }
-         println!(
-            "primos encontrados: {}", 
-            resultados.len()
-         );
+         ignicao(a, qtd_sp, pula_confirmacao);
       } Funcao::Processo => {
          let i = filtra_intervalo(argumentos);
          let dados = simultaneadade(i, QTD_THREADS);
@@ -283,32 +888,18 @@ fn forques_demanados(tipo: Funcao) {
          deleta_caminho(caminho.to_path_buf(), tempo);
       } Funcao::NovaChamada => {
          let a = filtra_intervalo(argumentos);
-         let mut geral = divide_intervalo(a, qtd_sp);
-         let mut forques: Vec<Child>;
-         forques = Vec::with_capacity(qtd_sp + 3);
-
-         for (o, i) in geral.drain(..).enumerate() {
-            println!("{}º. {:#?}", (o+1), i);
-            let processo = gera_processo(i).unwrap(); 
-            forques.push(processo); 
-         }
-
-         let mut resultados = Primos::with_capacity(30_000);
-         for sp in forques.iter_mut() { 
-            let r = colhe_resultado(sp);
-            resultados.extend(r); 
-         }
-
-         println!(
-            "primos encontrados: {}", 
-            resultados.len()
-         );
+         ignicao(a, qtd_sp, pula_confirmacao);
       } Funcao::PoderTotal => {
-         let dados = varre(filtra_intervalo(argumentos));
+         // todos processos da piscina no mesmo intervalo.
          /* como trecho é geralmente chamado
           * via fork, então todo conteúdo em
-          * bytes é despejado via saída padrão.  */
-         despeja_bytes(dados);
+          * bytes é despejado via saída padrão; e
+          * a falha vai para a saída de erro. */
+         match varre_em_paralelo(filtra_intervalo(argumentos)) {
+            Ok(dados) => despeja_bytes(dados),
+            Err(erro) => 
+               { eprintln!("a varredura em paralelo falhou: {}", erro); }
+         };
       }
    };
 }
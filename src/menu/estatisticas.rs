@@ -0,0 +1,203 @@
+/*! Estatísticas dos primos do banco de dados: a função de contagem π(x)
+ em cada potência de dez, comparada com as aproximações x/ln x e Li(x); a
+ densidade dos primos por década; e a contagem das classes de resto
+ módulo 4 e 3, onde aparece o viés de Chebyshev(as classes 4k+3 e 3k+2
+ quase sempre à frente). Tudo numa única passada pelos primos, sem que
+ fiquem na memória.
+*/
+
+use std::fmt::{Display, Formatter, Result as Resultado};
+use std::io;
+
+// constante de Euler-Mascheroni.
+const GAMMA: f64 = 0.577_215_664_901_532_9;
+
+/** A contagem até um marco `x`: π(x), e quantos primos de cada classe de
+ resto, [4k+1, 4k+3] e [3k+1, 3k+2]. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marco { 
+   pub x: u64, pub pi: u64, 
+   pub mod4: [u64; 2], pub mod3: [u64; 2] 
+}
+
+/** Quantos primos há na década de `inicio` a `fim`(inclusos); a última
+ pode estar incompleta, parando onde o BD parou. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decada { pub inicio: u64, pub fim: u64, pub qtd: u64 }
+
+impl Decada {
+   /** Fração dos números da década que são primos. */
+   pub fn densidade(&self) -> f64
+      { self.qtd as f64 / (self.fim - self.inicio + 1) as f64 }
+}
+
+/** Um real já com a quantia de casas, e o sufixo, com que é mostrado; a
+ tabela só aceita valores que são `Copy`. */
+#[derive(Debug, Clone, Copy)]
+pub struct Real { pub valor: f64, pub casas: usize, pub sufixo: &'static str }
+
+impl Display for Real {
+   fn fmt(&self, molde: &mut Formatter<'_>) -> Resultado
+      { write!(molde, "{:.*}{}", self.casas, self.valor, self.sufixo) }
+}
+
+/** A integral logarítmica li(x), pela série de Ramanujan, que converge
+ rápido para todo x > 1. */
+pub fn li(x: f64) -> f64 {
+   let ln = x.ln();
+   let (mut soma, mut termo, mut impares) = (0.0, 1.0, 0.0);
+
+   for n in 1..=300 {
+      // (-1)^(n-1) (ln x)^n / (n! 2^(n-1)), aos poucos.
+      termo *= if n == 1 { ln } else { -ln / (2.0 * n as f64) };
+      if (n - 1) % 2 == 0
+         { impares += 1.0 / n as f64; }
+      let parcela = termo * impares;
+      soma += parcela;
+      if parcela.abs() < soma.abs() * 1e-17
+         { break; }
+   }
+   GAMMA + ln.ln() + x.sqrt() * soma
+}
+
+/** A integral logarítmica "deslocada", Li(x) = li(x) - li(2). */
+#[allow(non_snake_case)]
+pub fn Li(x: f64) -> f64
+   { li(x) - li(2.0) }
+
+/** Erro relativo, em percentual, da aproximação dada. */
+pub fn erro_relativo(aproximado: f64, real: u64) -> f64
+   { 100.0 * (aproximado - real as f64) / real as f64 }
+
+/* As décadas, de 1 a 9, de 10 a 99, e assim por diante, até o limite;
+ * a última termina nele. */
+fn decadas_ate(limite: u64) -> Vec<Decada> {
+   let mut decadas = Vec::new();
+   let mut inicio: u64 = 1;
+
+   while inicio <= limite {
+      let fim = {
+         inicio.checked_mul(10)
+         .map_or(u64::MAX, |f| f - 1)
+         .min(limite)
+      };
+      decadas.push(Decada { inicio, fim, qtd: 0 });
+      if fim == limite
+         { break; }
+      inicio = fim + 1;
+   }
+   decadas
+}
+
+/**
+ Percorre os primos dados, em ordem, até o `limite`(até onde o BD cobre
+ tudo desde o dois), e conta π(x) e as classes de resto em cada potência
+ de dez, e no próprio limite; assim como os primos de cada década. Um
+ primo que não é maior que o anterior(repetido, ou de um registro fora de
+ ordem) não conta. O primeiro erro da leitura interrompe tudo.
+*/
+pub fn calcula<I>(primos: I, limite: u64) 
+  -> io::Result<(Vec<Marco>, Vec<Decada>)>
+  where I: Iterator<Item = io::Result<u64>>
+{
+   let mut decadas = decadas_ate(limite);
+   let mut marcos: Vec<Marco> = Vec::with_capacity(decadas.len() + 1);
+   let mut atual = Marco { x: 0, pi: 0, mod4: [0; 2], mod3: [0; 2] };
+   let mut anterior = 0;
+   let mut i = 0;
+
+   for primo in primos {
+      let primo = primo?;
+      if primo > limite
+         { break; }
+      if primo <= anterior
+         { continue; }
+      anterior = primo;
+      // a década acabou: a próxima potência de dez tem a contagem dela.
+      while primo > decadas[i].fim {
+         marcos.push(Marco { x: decadas[i].fim + 1, ..atual });
+         i += 1;
+      }
+      decadas[i].qtd += 1;
+      atual.pi += 1;
+      // 2 e 3 não entram em classe nenhuma.
+      if primo % 4 != 2
+         { atual.mod4[(primo % 4 / 2) as usize] += 1; }
+      if primo % 3 != 0
+         { atual.mod3[(primo % 3 - 1) as usize] += 1; }
+   }
+   // as décadas do fim, que já não tinham primos.
+   for decada in decadas.iter().take(decadas.len().saturating_sub(1)).skip(i)
+      { marcos.push(Marco { x: decada.fim + 1, ..atual }); }
+   if limite > 0 && marcos.last().is_none_or(|m| m.x != limite)
+      { marcos.push(Marco { x: limite, ..atual }); }
+   Ok((marcos, decadas))
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   fn primos_ate(n: u64) -> Vec<u64> {
+      (2..=n).filter(|&k| {
+         (2..k).take_while(|d| d * d <= k).all(|d| k % d != 0)
+      }).collect()
+   }
+
+   #[test]
+   fn IntegralLogaritmica() {
+      // valores de referência: li(10^3) e li(10^6).
+      assert!((li(1e3) - 177.609_657_990_152).abs() < 1e-6);
+      assert!((li(1e6) - 78_627.549_159_462_18).abs() < 1e-4);
+      assert!((li(2.0) - 1.045_163_780_117_49).abs() < 1e-12);
+      assert!(Li(2.0).abs() < 1e-12);
+      // até onde um u64 chega, ainda com precisão.
+      assert!((li(1e18) / 2.473_995_430_969_04e16 - 1.0).abs() < 1e-12);
+   }
+
+   #[test]
+   fn ContagemNasPotenciasDeDez() {
+      // o limite numa potência de dez não se repete.
+      let (marcos, decadas) = {
+         calcula(primos_ate(100).into_iter().map(Ok), 100).unwrap()
+      };
+      let xs: Vec<u64> = marcos.iter().map(|m| m.x).collect();
+      assert_eq!(xs, vec![10, 100]);
+      let ultima = Decada { inicio: 100, fim: 100, qtd: 0 };
+      assert_eq!(decadas.last(), Some(&ultima));
+
+      let primos = primos_ate(12_000);
+      let (marcos, decadas) = {
+         calcula(primos.iter().map(|&p| Ok(p)), 12_000).unwrap()
+      };
+      let contagens: Vec<(u64, u64)> = {
+         marcos.iter().map(|m| (m.x, m.pi)).collect()
+      };
+      assert_eq!(
+         contagens,
+         vec![(10, 4), (100, 25), (1000, 168), (10_000, 1229), (12_000, 1438)]
+      );
+      assert_eq!(marcos[2].mod4, [80, 87]);
+      assert_eq!(marcos[3].mod3, [611, 617]);
+      let qtds: Vec<u64> = decadas.iter().map(|d| d.qtd).collect();
+      assert_eq!(qtds, vec![4, 21, 143, 1061, 209]);
+      assert_eq!((decadas[4].inicio, decadas[4].fim), (10_000, 12_000));
+   }
+
+   #[test]
+   fn RepetidosNaoContam() {
+      // o 7 na borda de dois registros, e um 5 fora de ordem.
+      let primos = vec![2, 3, 5, 7, 7, 11, 5, 13, 17, 19, 23, 29, 31];
+      let (marcos, decadas) = {
+         calcula(primos.into_iter().map(Ok), 31).unwrap()
+      };
+      let contagens: Vec<(u64, u64)> = {
+         marcos.iter().map(|m| (m.x, m.pi)).collect()
+      };
+      assert_eq!(contagens, vec![(10, 4), (31, 11)]);
+      assert_eq!(marcos[1].mod4, [4, 6]);
+      assert_eq!(decadas[1].qtd, 7);
+   }
+}
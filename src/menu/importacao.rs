@@ -0,0 +1,170 @@
+/*! Lê listas de primos feitas por outros programas(a saída do primesieve,
+ ou qualquer lista em texto), ou o fluxo de inteiros que o próprio
+ `despeja_bytes` emite, e as confere antes de irem para o BD: cada valor
+ tem que ser primo, a lista estritamente crescente, e sem faltar nenhum
+ primo entre o primeiro e o último dela.
+*/
+
+use super::stream_serializado::parse_bytes;
+use crate::motor::{crivo_segmentado, e_primo_mr, Primos};
+use std::io::{self, Error, ErrorKind};
+
+// Quantos números são crivados por vez na conferência da lista.
+const JANELA: u64 = 1 << 20;
+/* Até onde a lista é comparada com o crivo; acima disso os primos-base
+ * dele ficam caros, e cada lacuna é testada número a número. */
+const LIMITE_DO_CRIVO: u64 = 1 << 48;
+
+/* Texto são só algarismos e espaços(ou quebras de linha); o resto é
+ * tomado como o fluxo binário. */
+fn e_texto(bytes: &[u8]) -> bool {
+   bytes.iter().all(|b| b.is_ascii_digit() || b.is_ascii_whitespace())
+}
+
+fn invalido(msg: String) -> Error
+   { Error::new(ErrorKind::InvalidData, msg) }
+
+/** Os números do conteúdo de um arquivo, seja em texto, separados por
+ espaços ou linhas, seja em binário, oito bytes(BigEndian) cada. */
+pub fn le_numeros(bytes: Vec<u8>) -> io::Result<Primos> {
+   if e_texto(&bytes[..]) {
+      // só algarismos, então é ASCII.
+      let texto = String::from_utf8_lossy(&bytes[..]);
+      return texto.split_ascii_whitespace().map(|numero| {
+         numero.parse::<u64>().map_err(|_| {
+            invalido(format!("'{}' não cabe em 64-bits", numero))
+         })
+      }).collect();
+   }
+   if !bytes.len().is_multiple_of(8) {
+      let msg = format!(
+         "não é uma lista em texto, nem um fluxo de inteiros de 8 bytes\
+         ({} bytes)", bytes.len()
+      );
+      return Err(invalido(msg));
+   }
+   Ok(parse_bytes(bytes))
+}
+
+/* Compara a lista, janela por janela, com o que o crivo acha na mesma
+ * faixa: o que sobra na lista não é primo, o que sobra no crivo falta. */
+fn confere_com_crivo(numeros: &[u64]) -> io::Result<()> {
+   let fim = numeros[numeros.len() - 1];
+   let mut restantes = numeros.iter().copied().peekable();
+   let mut a = numeros[0];
+
+   loop {
+      let b = a.saturating_add(JANELA - 1).min(fim);
+      for primo in crivo_segmentado(a, b) {
+         match restantes.next() {
+            Some(n) if n == primo => (),
+            Some(n) if n < primo =>
+               { return Err(invalido(format!("{} não é primo", n))); }
+            _ => {
+               let msg = format!("falta o primo {} na lista", primo);
+               return Err(invalido(msg));
+            }
+         };
+      }
+      if let Some(n) = restantes.next_if(|&n| n <= b)
+         { return Err(invalido(format!("{} não é primo", n))); }
+      if b == fim
+         { return Ok(()); }
+      a = b + 1;
+   }
+}
+
+/* Testa cada valor, e cada número entre dois seguidos da lista. */
+fn confere_lacunas(numeros: &[u64]) -> io::Result<()> {
+   for (k, &n) in numeros.iter().enumerate() {
+      if !e_primo_mr(n)
+         { return Err(invalido(format!("{} não é primo", n))); }
+      let proximo = match numeros.get(k + 1) {
+         Some(&p) => p,
+         None => break
+      };
+      if let Some(primo) = (n + 1..proximo).find(|&m| e_primo_mr(m)) {
+         let msg = format!("falta o primo {} na lista", primo);
+         return Err(invalido(msg));
+      }
+   }
+   Ok(())
+}
+
+/** Confere se a lista só tem primos, em ordem crescente, sem repetidos, e
+ sem faltar nenhum primo entre o primeiro e o último valor; o primeiro que
+ não presta é o erro. */
+pub fn confere_lista(numeros: &[u64]) -> io::Result<()> {
+   let mut anterior: Option<u64> = None;
+
+   for &n in numeros {
+      if anterior.is_some_and(|a| a >= n) {
+         let msg = format!("{} fora de ordem(ou repetido)", n);
+         return Err(invalido(msg));
+      }
+      anterior = Some(n);
+   }
+   match anterior {
+      None => Ok(()),
+      Some(fim) if fim <= LIMITE_DO_CRIVO => confere_com_crivo(numeros),
+      Some(_) => confere_lacunas(numeros)
+   }
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn TextoEBinario() {
+      let texto = b"2 3\n5\n\n7\t11\r\n".to_vec();
+      assert_eq!(le_numeros(texto).unwrap(), vec![2, 3, 5, 7, 11]);
+
+      let binario: Vec<u8> = {
+         [13u64, 17, 10_000_000_019].iter()
+         .flat_map(|n| n.to_be_bytes())
+         .collect()
+      };
+      assert_eq!(le_numeros(binario).unwrap(), vec![13, 17, 10_000_000_019]);
+      assert!(le_numeros(Vec::new()).unwrap().is_empty());
+
+      // grande demais, nem texto nem binário, e binário cortado.
+      assert!(le_numeros(b"18446744073709551616".to_vec()).is_err());
+      assert!(le_numeros(b"2, 3, 5".to_vec()).is_err());
+      assert!(le_numeros(vec![0xff; 12]).is_err());
+   }
+
+   #[test]
+   fn SoPrimosEmOrdem() {
+      assert!(confere_lista(&[2, 3, 5, 7, 11]).is_ok());
+      assert!(confere_lista(&[18_446_744_073_709_551_557]).is_ok());
+      assert!(confere_lista(&[]).is_ok());
+      assert!(confere_lista(&[2, 3, 9, 11]).is_err());
+      assert!(confere_lista(&[2, 5, 3]).is_err());
+      assert!(confere_lista(&[2, 3, 3]).is_err());
+      assert!(confere_lista(&[1]).is_err());
+      assert!(confere_lista(&[18_446_744_073_709_551_556]).is_err());
+   }
+
+   #[test]
+   fn ListaComLacunaERejeitada() {
+      let erro = confere_lista(&[2, 97]).unwrap_err();
+      assert!(erro.to_string().contains("falta o primo 3"));
+      assert!(confere_lista(&[2, 3, 5, 7, 18_446_744_073_709_551_557]).is_err());
+      // os dois últimos primos dos 64-bits, sem o do meio.
+      let grandes = [18_446_744_073_709_551_533, 18_446_744_073_709_551_557];
+      assert!(confere_lista(&grandes).is_ok());
+      // uma lista completa, passando de várias janelas do crivo.
+      let primos = crivo_segmentado(3_000_000, 3_000_000 + 3 * JANELA);
+      assert!(confere_lista(&primos[..]).is_ok());
+      let mut falta_um = primos.clone();
+      falta_um.remove(falta_um.len() / 2);
+      assert!(confere_lista(&falta_um[..]).is_err());
+      // um composto no lugar de um primo.
+      let mut composto = primos.clone();
+      composto[7] += 1;
+      assert!(confere_lista(&composto[..]).is_err());
+   }
+}
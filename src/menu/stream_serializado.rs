@@ -41,22 +41,15 @@ pub fn despeja_bytes(conjunto: Primos) {
    mem::drop(saida);
 }
 
-#[allow(clippy::suspicious_else_formatting)]
-fn parse_bytes(mut array: Bytes) -> Vec<u64> {
+/* Cada oito bytes(em BigEndian) formam um inteiro; um resto incompleto
+ * no final é ignorado. */
+pub(super) fn parse_bytes(array: Bytes) -> Vec<u64> {
    let mut a: [u8; 8] = [0; 8];
-   let mut lista: Vec<u64> = Vec::new();
-
-   let mut contador: usize = 0;
-   for byte in array.drain(..) {
-      if contador == 7 {
-         lista.push(u64::from_be_bytes(a));
-         contador = 0;
-      } else 
-         { a[contador] = byte; }
-      contador += 1;
-   }
 
-   return lista;
+   array.chunks_exact(8).map(|bytes| {
+      a.copy_from_slice(bytes);
+      u64::from_be_bytes(a)
+   }).collect()
 }
 
 /* Transforma array de bytes lida em inteiros positivos de 64-bits, que 
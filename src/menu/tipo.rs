@@ -4,6 +4,7 @@
 use std::str::FromStr;
 use std::time::Duration;
 use std::fmt::Error;
+use crate::banco::FormatoExportacao;
 
 
 #[derive(Debug)]
@@ -20,6 +21,26 @@ pub enum Funcao {
    PoderTotal, NovaChamada
 }
 
+// o que fazer com os backups.
+#[derive(Debug, PartialEq)]
+pub enum AcaoBackup {
+   // faz um novo backup(e poda os antigos).
+   Realiza,
+   // lista todos, e quais a retenção podaria.
+   Lista,
+   // poda os que a retenção não mantém; simulado não apaga nada.
+   Poda { simulado: bool }
+}
+
+// como o BD guarda os primos.
+#[derive(Debug, PartialEq)]
+pub enum ModoBD {
+   // a lista de sempre, registro por registro.
+   Lista,
+   // mapa de bits da roda de 30, nas faixas densas.
+   Roda
+}
+
 // argumentos comuns ao executar o programa.
 #[derive(Debug)]
 pub enum Argumentos {
@@ -31,8 +52,35 @@ pub enum Argumentos {
    ProcuraTempo(f32),
    // faz varredura por dada quantia demandada.
    ProcuraQtd(u64),
-   // 'arm' para futuro backup, hoje apenas cópia para novo BD.
-   Backup,
+   // backup do BD num pacote, listagem e poda deles.
+   Backup(AcaoBackup),
+   // restaura o BD de um backup(o dado, ou escolhido numa listagem).
+   Restaura(Option<String>),
+   // verifica se o número dado é primo(Miller-Rabin).
+   Verifica(u64),
+   // o k-ésimo primo guardado no BD(via índice).
+   Enesimo(u64),
+   // os primos guardados no BD no intervalo fechado dado(via índice).
+   Entre(u64, u64),
+   /* confere a integridade do BD: todos primos, ou só uma amostra; e 
+    * conserta o que for possível. */
+   VerificaBD { completa: bool, reparar: bool },
+   // varre as faixas que nenhum registro do BD cobre.
+   Preenche,
+   // funde os registros pequenos, até a quantia de primos dada em cada.
+   Compacta(Option<usize>),
+   // converte o BD para o modo dado(e com backup antes).
+   Converte(ModoBD),
+   /* exporta os primos(ou os registros) da faixa dada, para o arquivo 
+    * dado, ou a saída padrão. */
+   Exporta {
+      formato: FormatoExportacao, registros: bool,
+      de: u64, ate: u64, saida: Option<String>
+   },
+   // grava no BD os primos do arquivo dado(em texto, ou binário).
+   Importa(String),
+   // π(x), densidade e classes de resto dos primos do BD.
+   Estatisticas,
    // ajuda, info de como o programa funciona.
    Ajuda,
    /* não aberto ao público apenas para 
@@ -125,12 +173,56 @@ fn apenas_numerica(arg:&str) -> bool {
    return true;
 }
 
+/* As marcações do 'exporta', em qualquer ordem; nada se alguma for
+ * desconhecida, ou tiver um valor inválido. */
+fn argumentos_de_exportacao(marcacoes: &[String]) -> Option<Argumentos> {
+   let mut formato = FormatoExportacao::Txt;
+   let (mut de, mut ate) = (0, u64::MAX);
+   let mut saida: Option<String> = None;
+   let mut registros = false;
+   let mut marcacoes = marcacoes.iter();
+
+   while let Some(marcacao) = marcacoes.next() {
+      if marcacao == "--registros"
+         { registros = true; continue; }
+      let valor = marcacoes.next()?;
+      match marcacao.as_str() {
+         "--formato" => 
+            { formato = FormatoExportacao::de_nome(valor)?; }
+         "--de" => { de = valor.parse().ok()?; }
+         "--ate" => { ate = valor.parse().ok()?; }
+         "-o" => { saida = Some(valor.clone()); }
+         _ => return None
+      };
+   }
+   if de > ate
+      { return None; }
+   Some(Argumentos::Exporta { formato, registros, de, ate, saida })
+}
+
 /** Retorna um tipo de enum mais adequado para os dados argumentos 
  * passados. */
 #[allow(clippy::suspicious_else_formatting)]
 pub fn transforma(argumentos:&Vec<String>) -> Argumentos {
    let total = argumentos.len();
 
+   // a única opção que aceita várias marcações, em qualquer ordem.
+   if total >= 2 && argumentos[1] == "verifica-bd" {
+      let marcacoes = &argumentos[2..];
+      let completa = marcacoes.iter().any(|s| s == "--completa");
+      let reparar = marcacoes.iter().any(|s| s == "--reparar");
+      let conhecidas = completa as usize + reparar as usize;
+
+      if conhecidas != marcacoes.len() 
+         { return Argumentos::Ajuda; }
+      return Argumentos::VerificaBD { completa, reparar };
+   }
+   if total >= 2 && argumentos[1] == "exporta" {
+      return match argumentos_de_exportacao(&argumentos[2..]) {
+         Some(exporta) => exporta,
+         None => Argumentos::Ajuda
+      };
+   }
    /* Se nenhum argumento for cedido, mostrar opção 'ajuda'.
     * incompleta, retorna pedido de ajuda. */
    if total == 1 
@@ -150,7 +242,15 @@ pub fn transforma(argumentos:&Vec<String>) -> Argumentos {
          { Argumentos::Privado(Funcao::Inversao) }
       // continuação das opções "públicas".
       else if argumentos[1] == "backup"
-         { Argumentos::Backup }
+         { Argumentos::Backup(AcaoBackup::Realiza) }
+      else if argumentos[1] == "restaura"
+         { Argumentos::Restaura(None) }
+      else if argumentos[1] == "preenche"
+         { Argumentos::Preenche }
+      else if argumentos[1] == "compacta"
+         { Argumentos::Compacta(None) }
+      else if argumentos[1] == "estatisticas"
+         { Argumentos::Estatisticas }
       else 
          { Argumentos::Ajuda }
    } else if total == 3 {
@@ -167,6 +267,36 @@ pub fn transforma(argumentos:&Vec<String>) -> Argumentos {
       } else if opcao == "quantia" && valor_numerico {
          let valor:u64 = arg.parse().unwrap();
          Argumentos::ProcuraQtd(valor)
+      } else if opcao == "verifica" && valor_numerico {
+         match arg.parse::<u64>() {
+            Ok(valor) => Argumentos::Verifica(valor),
+            Err(_) => { 
+               println!("o valor dado não cabe em 64-bits!");
+               Argumentos::Ajuda 
+            }
+         }
+      } else if opcao == "enesimo" && valor_numerico {
+         match arg.parse::<u64>() {
+            Ok(k) if k > 0 => Argumentos::Enesimo(k),
+            _ => Argumentos::Ajuda
+         }
+      } else if opcao == "backup" && arg == "--listar" {
+         Argumentos::Backup(AcaoBackup::Lista)
+      } else if opcao == "backup" && arg == "--podar" {
+         Argumentos::Backup(AcaoBackup::Poda { simulado: false })
+      } else if opcao == "compacta" && valor_numerico {
+         match arg.parse::<usize>() {
+            Ok(limite) if limite > 0 => Argumentos::Compacta(Some(limite)),
+            _ => Argumentos::Ajuda
+         }
+      } else if opcao == "restaura" {
+         Argumentos::Restaura(Some(arg.clone()))
+      } else if opcao == "importa" {
+         Argumentos::Importa(arg.clone())
+      } else if opcao == "converte" && arg == "roda" {
+         Argumentos::Converte(ModoBD::Roda)
+      } else if opcao == "converte" && arg == "lista" {
+         Argumentos::Converte(ModoBD::Lista)
       } else if opcao  == "ignição" { 
          Argumentos::Privado(Funcao::Chamada)
       } else if opcao == "varre" {
@@ -179,8 +309,16 @@ pub fn transforma(argumentos:&Vec<String>) -> Argumentos {
          { Argumentos::Ajuda }
    } else if total == 4 {
       let opcao = &argumentos[1];
+      let args = (argumentos[2].as_str(), argumentos[3].as_str());
       if opcao == "função-deleta-caminho" {
          Argumentos::Privado(Funcao::Deleta)
+      } else if opcao == "backup" && args == ("--podar", "--simulado") {
+         Argumentos::Backup(AcaoBackup::Poda { simulado: true })
+      } else if opcao == "entre" {
+         match (args.0.parse::<u64>(), args.1.parse::<u64>()) {
+            (Ok(a), Ok(b)) if a <= b => Argumentos::Entre(a, b),
+            _ => Argumentos::Ajuda
+         }
       } else 
          { Argumentos::Ajuda }
    }else { 
@@ -238,6 +376,13 @@ fn argumento_em_tempo(string:&str) -> Result<Duration, Error> {
 mod tests {
    use super::*;
 
+   // A linha de comando, como chega ao programa.
+   fn entrada(args: &[&str]) -> Vec<String> {
+      let mut entrada = vec!["primos".to_string()];
+      entrada.extend(args.iter().map(|s| s.to_string()));
+      entrada
+   }
+
    #[test]
    #[allow(non_snake_case)]
    fn ArgumentoValido() {
@@ -264,4 +409,143 @@ mod tests {
       assert!(!argumento_valido("28segs"));
       assert!(!argumento_valido("37mins"));
    }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcaoVerifica() {
+      match transforma(&entrada(&["verifica", "18446744073709551557"])) {
+         Argumentos::Verifica(n) => assert_eq!(n, 18_446_744_073_709_551_557),
+         _ => panic!("deveria ser a opção 'verifica'")
+      };
+      // maior que 64-bits, ou não numérico.
+      assert!(matches!(
+         transforma(&entrada(&["verifica", "18446744073709551616"])),
+         Argumentos::Ajuda
+      ));
+      assert!(matches!(
+         transforma(&entrada(&["verifica", "12a"])), Argumentos::Ajuda
+      ));
+   }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcoesDeConsultaAoIndice() {
+
+      assert!(matches!(
+         transforma(&entrada(&["enesimo", "10000"])),
+         Argumentos::Enesimo(10_000)
+      ));
+      // não há o "zerésimo" primo.
+      assert!(matches!(
+         transforma(&entrada(&["enesimo", "0"])), Argumentos::Ajuda
+      ));
+      assert!(matches!(
+         transforma(&entrada(&["entre", "100", "200"])),
+         Argumentos::Entre(100, 200)
+      ));
+      assert!(matches!(
+         transforma(&entrada(&["entre", "200", "100"])), Argumentos::Ajuda
+      ));
+      assert!(matches!(
+         transforma(&entrada(&["entre", "-1", "100"])), Argumentos::Ajuda
+      ));
+   }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcaoRestaura() {
+      assert!(matches!(
+         transforma(&entrada(&["restaura"])), Argumentos::Restaura(None)
+      ));
+      match transforma(&entrada(&["restaura", "iii"])) {
+         Argumentos::Restaura(Some(b)) => assert_eq!(b, "iii"),
+         _ => panic!("deveria ser a opção 'restaura'")
+      };
+      // o 'importa' também só leva um caminho.
+      match transforma(&entrada(&["importa", "primos.txt"])) {
+         Argumentos::Importa(arquivo) => assert_eq!(arquivo, "primos.txt"),
+         _ => panic!("deveria ser a opção 'importa'")
+      };
+   }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcaoBackup() {
+      let acao = |args: &[&str]| match transforma(&entrada(args)) {
+         Argumentos::Backup(acao) => Some(acao),
+         _ => None
+      };
+
+      assert_eq!(acao(&["backup"]), Some(AcaoBackup::Realiza));
+      assert_eq!(acao(&["backup", "--listar"]), Some(AcaoBackup::Lista));
+      assert_eq!(
+         acao(&["backup", "--podar"]), 
+         Some(AcaoBackup::Poda { simulado: false })
+      );
+      assert_eq!(
+         acao(&["backup", "--podar", "--simulado"]), 
+         Some(AcaoBackup::Poda { simulado: true })
+      );
+      assert_eq!(acao(&["backup", "--apagar"]), None);
+   }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcaoConverte() {
+      let modo = |arg: &str| match transforma(&entrada(&["converte", arg])) {
+         Argumentos::Converte(modo) => Some(modo),
+         _ => None
+      };
+
+      assert_eq!(modo("roda"), Some(ModoBD::Roda));
+      assert_eq!(modo("lista"), Some(ModoBD::Lista));
+      assert_eq!(modo("bitmap"), None);
+   }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcaoExporta() {
+      assert!(matches!(
+         transforma(&entrada(&["exporta"])),
+         Argumentos::Exporta { 
+            formato: FormatoExportacao::Txt, registros: false,
+            de: 0, ate: u64::MAX, saida: None
+         }
+      ));
+      match transforma(&entrada(&[
+         "exporta", "-o", "primos.json", "--ate", "1000", "--formato", "json",
+         "--registros", "--de", "10"
+      ])) {
+         Argumentos::Exporta { formato, registros, de, ate, saida } => {
+            assert_eq!(formato, FormatoExportacao::Json);
+            assert!(registros);
+            assert_eq!((de, ate), (10, 1000));
+            assert_eq!(saida.as_deref(), Some("primos.json"));
+         } _ => panic!("deveria ser a opção 'exporta'")
+      };
+      // formato desconhecido, sem valor, ou faixa invertida.
+      for args in [
+         &["exporta", "--formato", "xml"][..], &["exporta", "--de"],
+         &["exporta", "--de", "9", "--ate", "3"]
+      ] {
+         assert!(matches!(transforma(&entrada(args)), Argumentos::Ajuda));
+      }
+   }
+
+   #[test]
+   #[allow(non_snake_case)]
+   fn OpcaoVerificaBD() {
+      assert!(matches!(
+         transforma(&entrada(&["verifica-bd"])),
+         Argumentos::VerificaBD { completa: false, reparar: false }
+      ));
+      assert!(matches!(
+         transforma(&entrada(&["verifica-bd", "--reparar", "--completa"])),
+         Argumentos::VerificaBD { completa: true, reparar: true }
+      ));
+      assert!(matches!(
+         transforma(&entrada(&["verifica-bd", "--rapido"])),
+         Argumentos::Ajuda
+      ));
+   }
 }
@@ -14,44 +14,138 @@ mod constantes;
 mod tredi;
 mod paraleliza;
 mod algoritmo;
+mod crivo;
+mod interrupcao;
+mod piscina;
 // Subindo todas features dos submódulos para cá.
 pub use tredi::*;
 pub use paraleliza::*;
 pub use algoritmo::*;
 pub use constantes::*;
+pub use crivo::*;
+pub use interrupcao::*;
+pub use piscina::*;
 
 
+// Quantos números cada janela do crivo cobre: mil buscas de cem.
+const JANELA: u64 = 100 * 1_000;
+
+/* Quantas buscas de cem números cobrem do número seguinte ao 'inicio' até
+ * o 'unv'. */
+fn buscas_ate(inicio: u64, unv: u64) -> u64
+   { (unv - inicio).div_ceil(100) }
+
+/** Salvamentos periódicos de uma varredura ainda em andamento, assim uma 
+  varredura longa não é perdida inteira numa queda do programa. A cada 
+  'periodo', ou a cada 'qtd' novos primos, o que vier primeiro, os dados 
+  parciais são entregues para a função 'salva', junto do ínicio da 
+  varredura. */
+pub struct PontoDeSalvamento {
+   pub periodo: Duration,
+   pub qtd: usize,
+   pub salva: fn(u64, &Dados),
+   /* ínicio e dados de uma varredura interrompida que está sendo retomada,
+    * cada salvamento já leva eles juntos. */
+   pub anterior: Option<(u64, Dados)>,
+   cronometro: Instant,
+   ultima_qtd: usize
+}
+
+impl PontoDeSalvamento {
+   pub fn novo(periodo: Duration, qtd: usize, salva: fn(u64, &Dados)) -> Self {
+      PontoDeSalvamento { 
+         periodo, qtd, salva, anterior: None,
+         cronometro: Instant::now(), ultima_qtd: 0 
+      }
+   }
+
+   /* Salva os dados parciais, se já deu o tempo, ou a quantia de primos
+    * desde o último salvamento. Os metadados são o último número 
+    * verificado, o tempo decorrido, e as buscas feitas até aqui. */
+   fn talvez_salva(&mut self, inicio: u64, lista: &[u64], 
+     metadados: (u64, u64, u64)) 
+   {
+      let novos = lista.len() - self.ultima_qtd;
+
+      if self.cronometro.elapsed() < self.periodo && novos < self.qtd
+         { return; }
+      // só copia a lista quando de fato vai salvar.
+      let parcial = (lista.to_vec(), metadados.0, metadados.1, metadados.2);
+      match self.anterior {
+         Some((origem, ref anterior)) => 
+            { (self.salva)(origem, &junta_varreduras(anterior, &parcial)); }
+         None => 
+            { (self.salva)(inicio, &parcial); }
+      };
+      self.cronometro = Instant::now();
+      self.ultima_qtd = lista.len();
+   }
+}
+
+/** Junta uma varredura com a sua continuação, somando o tempo e a quantia
+  de buscas feitas. */
+pub fn junta_varreduras(anterior: &Dados, continuacao: &Dados) -> Dados {
+   let mut lista = anterior.0.clone();
+   lista.extend(&continuacao.0[..]);
+   (
+      lista, continuacao.1, 
+      anterior.2 + continuacao.2, 
+      anterior.3 + continuacao.3
+   )
+}
+
 /** Retorna uma tupla contendo o último número verificado todos os dados 
   processados da varredura por números primos de um valor 'inicial' até 
-  que acha-se uma qunantia 'qtd' demandada. */
-pub fn busca_continua(inicio: u64, qtd: u64) -> Dados {
+  que acha-se uma qunantia 'qtd' demandada. O 'inicio' é o último número
+  já verificado, a varredura começa no seguinte; e acha exatamente 'qtd'
+  primos, o último deles sendo o último verificado. */
+pub fn busca_continua(inicio: u64, qtd: u64, 
+  mut salvamento: Option<&mut PontoDeSalvamento>) -> Dados {
     let mut lista: Vec<u64> = Vec::new();
-    // fim e ínicio de cada busca realizada.
-    let (mut i, mut f):(u64, u64) = (inicio, inicio + qtd);
-    // contador de buscas realizadas.
-    let mut buscas:u64 = 0;
+    // ínicio de cada janela, e o último número verificado.
+    let (mut i, mut unv): (u64, u64) = (inicio + 1, inicio);
     // barra de progresso.
     let mut barra = PP::cria(qtd);
     info_progresso_ii(&mut barra, 0, inicio);
 
     // tempo decorrido durante computagem...
     let tempo = Instant::now();
+    // um CTRL-C agora só para a varredura, não perde o que foi achado.
+    captura_interrupcoes();
 
     while (lista.len() as u64) < qtd {
-      // adiciona os primos achados.
-      lista.extend(primos_faixa(i, f));
+      // adiciona os primos achados(via crivo segmentado).
+      let f = i.saturating_add(JANELA - 1);
+      lista.extend(crivo_segmentado(i, f));
+      unv = f;
+      // passou da quantia: o que sobrou da janela fica para a próxima.
+      if (lista.len() as u64) > qtd {
+         lista.truncate(qtd as usize);
+         unv = *lista.last().unwrap();
+      }
 
       // barra de progresso.
       let tamanho = lista.len();
-      let ultimo_primo = lista[tamanho-1];
+      let ultimo_primo = lista.last().copied().unwrap_or(unv);
       info_progresso_ii(&mut barra, tamanho, ultimo_primo);
 
-      buscas += 1;  //contabilizando mais uma busca.
+      // salvamento periódico do que já foi achado.
+      if let Some(ponto) = salvamento.as_mut() {
+         let metadados = (unv, tempo.elapsed().as_secs(), buscas_ate(inicio, unv));
+         ponto.talvez_salva(inicio, &lista, metadados);
+      }
       // verificação intermediaria para interrupção.
       if (tamanho as u64) >= qtd { break; }
-      /* buscando mais cem números.
-       * e o novo ínicio é o antigo fim. */
-      i = f; f += 100;
+      if foi_interrompido() { 
+         println!("\nvarredura interrompida, parou no {}.", unv);
+         break; 
+      }
+      /* a próxima janela começa logo depois do fim desta, pois o crivo 
+       * inclui as duas pontas. */
+      match f.checked_add(1) {
+         Some(proximo) => i = proximo,
+         None => break
+      };
     }
 
     /* o retorno de dados é organizado do seguinte modo:
@@ -62,7 +156,8 @@ pub fn busca_continua(inicio: u64, qtd: u64) -> Dados {
      *     a total da quantia demandada inicialmente.
      * 4º) a quantia de buscas feitas, com raios de 100 
      *     números. */
-    (lista, f, tempo.elapsed().as_secs(), buscas)
+    libera_interrupcoes();
+    (lista, unv, tempo.elapsed().as_secs(), buscas_ate(inicio, unv))
 }
 
 fn info_progresso_ii(barra:&mut PP, atual: usize, primo:u64) {
@@ -95,15 +190,15 @@ fn info_progresso_iii(barra:&mut PT, primo: u64, percentual: f32) {
    }
 }
 
-/** Faz busca levando em consideração o tempo não uma quantia demandada. */
-pub fn busca_continua_temporizada(inicio: u64, tempo: Duration) -> Dados 
+/** Faz busca levando em consideração o tempo não uma quantia demandada. 
+  Como na `busca_continua`, começa no número seguinte ao 'inicio'. */
+pub fn busca_continua_temporizada(inicio: u64, tempo: Duration,
+  mut salvamento: Option<&mut PontoDeSalvamento>) -> Dados 
 {
     let mut lista = Vec::<u64>::new();
-    // fim e ínicio de cada busca realizada.
-    let (mut i, mut f): (u64, u64) = (inicio, inicio + 100);
-    // contador de buscas realizadas.
-    let mut buscas: u64 = 0;
-    let mut ultimo_primo: u64;
+    // ínicio de cada janela, e o último número verificado.
+    let (mut i, mut f): (u64, u64) = (inicio + 1, inicio);
+    let mut ultimo_primo: u64 = inicio;
     // tempo decorrido durante computagem...
     let contador = Instant::now();
     let pausa = Duration::from_secs_f32(3.7);
@@ -112,32 +207,45 @@ pub fn busca_continua_temporizada(inicio: u64, tempo: Duration) -> Dados
     let segs = Duration::from_secs_f32(0.500);
     let mut barra = PT::cria(tempo.as_secs(), segs);
     info_progresso_iii(&mut barra, inicio, 0.30);
+    // um CTRL-C agora só para a varredura, não perde o que foi achado.
+    captura_interrupcoes();
 
     loop {
-      // Anexando primos encontrados ...
-      lista.extend(primos_faixa(i, f));
+      // Anexando primos encontrados(via crivo segmentado) ...
+      f = i.saturating_add(JANELA - 1);
+      lista.extend(crivo_segmentado(i, f));
 
-      let n = lista.len();
-      let indice = n - 1;
       // Mostra o progresso da varredura...
-      ultimo_primo = lista[indice];
+      ultimo_primo = lista.last().copied().unwrap_or(ultimo_primo);
       info_progresso_iii(&mut barra, ultimo_primo, 0.52);
 
-      //Contabilizando mais uma busca.
-      buscas += 1; 
+      // Salvamento periódico do que já foi achado.
+      if let Some(ponto) = salvamento.as_mut() {
+         let buscas = buscas_ate(inicio, f);
+         let metadados = (f, contador.elapsed().as_secs(), buscas);
+         ponto.talvez_salva(inicio, &lista, metadados);
+      }
       // Se batido o tempo, para o laço infinito.
       if contador.elapsed() > tempo { break; }
-      // Buscando mais cem números. e o novo ínicio é o antigo fim.
-      i = f;  f += 100;
+      // Ou se foi pedido para parar.
+      if foi_interrompido() { 
+         println!("\nvarredura interrompida, parou no {}.", f);
+         break; 
+      }
+      // A próxima janela começa logo depois do fim desta.
+      match f.checked_add(1) {
+         Some(proximo) => i = proximo,
+         None => break
+      };
     }
 
+    // Registra tempo final(antes da pausa da barra).
+    let decorrido = contador.elapsed().as_secs();
+    libera_interrupcoes();
     std::thread::sleep(pausa);
     /* Para que a barra complete 100%, forçando uma última impressão de 
      * tela. */
     info_progresso_iii(&mut barra, ultimo_primo, 0.45);
-    // Registra tempo final.
-    let d = contador.elapsed();
-    let decorrido = { tempo.checked_sub(d).unwrap().as_secs() };
 
     /* O retorno de dados é organizado do seguinte modo:
      *
@@ -148,6 +256,83 @@ pub fn busca_continua_temporizada(inicio: u64, tempo: Duration) -> Dados
      *     a total da quantia demandada inicialmente.
      * 4º. a quantia de buscas feitas, com raios de 100 
      *     números. */
-    (lista, f, decorrido, buscas)
+    (lista, f, decorrido, buscas_ate(inicio, f))
 }
 
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use std::sync::atomic::{AtomicU64, Ordering};
+   use std::sync::Mutex;
+
+   /* as interrupções são globais ao processo, então os testes que fazem
+    * varreduras não podem rodar ao mesmo tempo. */
+   static VARREDURA: Mutex<()> = Mutex::new(());
+
+   // o último 'ínicio' e a quantia de primos entregues ao salvamento.
+   static INICIO: AtomicU64 = AtomicU64::new(0);
+   static SALVOS: AtomicU64 = AtomicU64::new(0);
+
+   fn salva_de_teste(inicio: u64, dados: &Dados) {
+      INICIO.store(inicio, Ordering::SeqCst);
+      SALVOS.store(dados.0.len() as u64, Ordering::SeqCst);
+   }
+
+   #[test]
+   fn JuntaVarreduras() {
+      let anterior = (vec![2, 3, 5, 7], 10, 3, 1);
+      let continuacao = (vec![11, 13, 17, 19], 20, 4, 2);
+      assert_eq!(
+         junta_varreduras(&anterior, &continuacao),
+         (vec![2, 3, 5, 7, 11, 13, 17, 19], 20, 7, 3)
+      );
+   }
+
+   #[test]
+   fn SalvamentosPeriodicosPorQuantia() {
+      let _vez = VARREDURA.lock().unwrap();
+      let periodo = Duration::from_secs(3600);
+      let mut ponto = PontoDeSalvamento::novo(periodo, 100, salva_de_teste);
+      let parcial = (vec![3, 5], 6, 1, 1);
+      ponto.anterior = Some((1, parcial));
+
+      let dados = busca_continua(6, 250, Some(&mut ponto));
+      // retomada: cada salvamento já leva a anterior junto.
+      assert_eq!(INICIO.load(Ordering::SeqCst), 1);
+      assert!(SALVOS.load(Ordering::SeqCst) >= 2 + 200);
+      assert!(SALVOS.load(Ordering::SeqCst) <= 2 + dados.0.len() as u64);
+   }
+
+   #[test]
+   fn JanelasNaoRepetemAsPontas() {
+      let _vez = VARREDURA.lock().unwrap();
+      // o 'inicio', mesmo primo, já foi verificado.
+      let (lista, unv, _, buscas) = busca_continua(7, 15_000, None);
+      assert_eq!(lista[0], 11);
+      assert_eq!(lista.len(), 15_000);
+      // várias janelas, e nenhum primo repetido nas pontas delas.
+      assert_eq!(lista, crivo_segmentado(8, unv));
+      assert_eq!(unv, *lista.last().unwrap());
+      assert_eq!(buscas, (unv - 7).div_ceil(100));
+   }
+
+   #[test]
+   fn InterrupcaoParaNaProximaJanela() {
+      let _vez = VARREDURA.lock().unwrap();
+      /* como se o CTRL-C tivesse chegado já no começo da varredura; sem
+       * mandar o sinal de fato, que iria para o processo inteiro. */
+      interrupcao::marca_interrupcao();
+      assert!(foi_interrompido());
+
+      let (lista, unv, _, buscas) = busca_continua(1_000, 100_000, None);
+      assert_eq!(buscas, JANELA / 100);
+      // o último verificado é o fim da única janela feita.
+      assert_eq!(unv, 1_000 + JANELA);
+      assert_eq!(lista, crivo_segmentado(1_000, unv));
+      // e tudo volta ao normal no final.
+      assert!(!foi_interrompido());
+   }
+}
@@ -92,6 +92,71 @@ pub fn e_primoI(n: u64) -> bool {
    return true;
 }
 
+/* Multiplicação modular sem estouro, fazendo a conta em 128-bits. */
+fn multiplica_mod(a: u64, b: u64, m: u64) -> u64 
+   { ((a as u128 * b as u128) % m as u128) as u64 }
+
+/* Exponenciação modular rápida(quadrados sucessivos). */
+fn potencia_mod(mut base: u64, mut expoente: u64, m: u64) -> u64 {
+   let mut resultado: u64 = 1;
+   base %= m;
+
+   while expoente > 0 {
+      if expoente & 1 == 1
+         { resultado = multiplica_mod(resultado, base, m); }
+      base = multiplica_mod(base, base, m);
+      expoente >>= 1;
+   }
+   resultado
+}
+
+/* Testemunhas que tornam o teste determinístico para qualquer valor de 
+ * 64-bits(na verdade, funciona até ~3,3 x 10²⁴). */
+const TESTEMUNHAS: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/**
+ Teste de primalidade de Miller-Rabin, determinístico para toda faixa de 
+ inteiros de 64-bits. Ao contrário de `e_primo` e `e_primoI`, responde 
+ instantaneamente mesmo para os valores mais altos.
+
+# Exemplos:
+```
+assert!(e_primo_mr(97));
+assert!(e_primo_mr(18_446_744_073_709_551_557));
+assert!(!e_primo_mr(18_446_744_073_709_551_615));
+```
+*/
+pub fn e_primo_mr(n: u64) -> bool {
+   if n < 2 
+      { return false; }
+   // primos pequenos, ou seus múltiplos, já são resolvidos aqui.
+   for p in TESTEMUNHAS {
+      if n % p == 0 
+         { return n == p; }
+   }
+
+   // decompondo n - 1 = d x 2^s, com 'd' ímpar.
+   let mut d = n - 1;
+   let mut s = 0;
+   while d % 2 == 0 
+      { d /= 2; s += 1; }
+
+   'testemunhas: for a in TESTEMUNHAS {
+      let mut x = potencia_mod(a, d, n);
+
+      if x == 1 || x == n - 1
+         { continue 'testemunhas; }
+      for _ in 1..s {
+         x = multiplica_mod(x, x, n);
+         if x == n - 1
+            { continue 'testemunhas; }
+      }
+      // 'a' é testemunha de que 'n' é composto.
+      return false;
+   }
+   true
+}
+
 /** Retorna uma array contendo todos primos na faixa passada.
 
  # Exemplos:
@@ -209,6 +274,24 @@ mod tests {
       }
    }
 
+   #[test]
+   fn MillerRabinConcordaComDivisaoPorTentativa() {
+      for n in 0u64..=100_000 
+         { assert_eq!(e_primo_mr(n), e_primoI(n), "falhou em {}", n); }
+   }
+
+   #[test]
+   fn MillerRabinValoresGrandes() {
+      // maior primo de 64-bits, e alguns compostos 'enganadores'.
+      assert!(e_primo_mr(18_446_744_073_709_551_557));
+      assert!(!e_primo_mr(u64::MAX));
+      assert!(e_primo_mr(4_294_967_291));
+      assert!(!e_primo_mr(3_215_031_751));
+      assert!(!e_primo_mr(3_825_123_056_546_413_051));
+      // quadrado de um primo de 32-bits.
+      assert!(!e_primo_mr(4_294_967_291 * 4_294_967_291));
+   }
+
    #[test]
    #[ignore="último trecho consome quase todo tempo!"]
    fn mesmosResultados() {
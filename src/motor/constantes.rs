@@ -7,8 +7,6 @@ extern crate utilitarios;
 
 use std::time::{Instant, Duration};
 use std::ops::{RangeInclusive};
-use std::collections::{HashSet};
-use std::thread::{JoinHandle};
 use std::process::{Child};
 use std::io::{Error};
 // Funções e estruturas externas:
@@ -24,10 +22,13 @@ pub type ListaDePrimos = Vec<u64>;
 pub type Dados = (ListaDePrimos, u64, u64, u64);
 /* Apelidos de pacotes de dados que são retornados ou recebidos nas funções
  * abaixo: */
-pub type Primos = HashSet<u64>;
+/* Sempre em ordem crescente, e sem repetições, assim como é guardado no
+ * banco de dados. */
+pub type Primos = Vec<u64>;
 pub type Intervalo = RangeInclusive<u64>;
 pub type Intervalos = Vec<Intervalo>;
-pub type Fios = Vec<JoinHandle<Primos>>;
+#[cfg(test)]
+pub type Fios = Vec<std::thread::JoinHandle<Primos>>;
 pub type Processo = Result<Child, Error>;
 /* Uma referência da tupla que registra dados, que pode ser solicitada 
  * ou não. */
@@ -0,0 +1,188 @@
+/*!
+ Crivo de Eratóstenes segmentado. Ao invés de testar número por número
+ via divisão por tentativa(ver `primos_faixa`), marca os múltiplos dos
+ primos-base dentro de janelas de tamanho fixo, assim a memória usada em
+ cada janela é limitada, não importa o tamanho da faixa pedida.
+*/
+
+// Tamanho de cada janela(segmento) que é crivada por vez.
+const TAMANHO_SEGMENTO: u64 = 1 << 15;
+
+/* Raíz quadrada inteira(piso), corrigindo o erro de arredondamento do
+ * ponto flutuante para valores grandes. */
+fn raiz_inteira(n: u64) -> u64 {
+   let mut r = (n as f64).sqrt() as u64;
+
+   // ajustando para baixo, caso tenha passado ...
+   while r.checked_mul(r).map_or(true, |q| q > n)
+      { r -= 1; }
+   // ou para cima, caso tenha ficado aquém.
+   while (r + 1).checked_mul(r + 1).is_some_and(|q| q <= n)
+      { r += 1; }
+   r
+}
+
+/* Crivo simples que acha todos primos até o 'limite', estes serão os
+ * primos-base usados para crivar cada segmento. */
+fn primos_base(limite: u64) -> Vec<u64> {
+   let limite = limite as usize;
+   let mut primos = Vec::<u64>::new();
+
+   if limite < 2
+      { return primos; }
+
+   let mut marcas = vec![true; limite + 1];
+   marcas[0] = false;
+   marcas[1] = false;
+
+   let mut p = 2;
+   while p * p <= limite {
+      if marcas[p] {
+         let mut multiplo = p * p;
+         while multiplo <= limite
+            { marcas[multiplo] = false; multiplo += p; }
+      }
+      p += 1;
+   }
+
+   for (numero, &primo) in marcas.iter().enumerate() {
+      if primo
+         { primos.push(numero as u64); }
+   }
+   primos
+}
+
+/* Primeiro múltiplo de 'p' que é maior ou igual a 'baixo', porém nunca
+ * menor que o quadrado de 'p'. Retorna nada se estoura os 64-bits. */
+fn primeiro_multiplo(p: u64, baixo: u64) -> Option<u64> {
+   let quadrado = p * p;
+
+   if quadrado >= baixo
+      { return Some(quadrado); }
+
+   match baixo % p {
+      0 => Some(baixo),
+      resto => baixo.checked_add(p - resto)
+   }
+}
+
+/** Retorna uma array contendo todos primos na faixa passada, assim como
+ `primos_faixa`, porém usando o crivo segmentado.
+
+ # Exemplos:
+ ```
+ let primos_1_a_30 = crivo_segmentado(1, 30);
+ assert_eq!(
+   vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29],
+   primos_1_a_30
+ );
+ ```
+*/
+pub fn crivo_segmentado(inicio: u64, fim: u64) -> Vec<u64> {
+   let mut primos = Vec::<u64>::new();
+
+   // faixas sem qualquer primo possível.
+   if fim < 2 || inicio > fim
+      { return primos; }
+
+   let base = primos_base(raiz_inteira(fim));
+   // reaproveitada em cada segmento.
+   let mut marcas = vec![true; TAMANHO_SEGMENTO as usize];
+   let mut baixo = inicio.max(2);
+
+   loop {
+      let alto = {
+         baixo.saturating_add(TAMANHO_SEGMENTO - 1)
+         .min(fim)
+      };
+      let n = (alto - baixo + 1) as usize;
+
+      marcas[..n].fill(true);
+      // riscando os múltiplos de cada primo-base neste segmento.
+      for &p in base.iter() {
+         if p * p > alto
+            { break; }
+         let mut multiplo = primeiro_multiplo(p, baixo);
+
+         while let Some(m) = multiplo {
+            if m > alto
+               { break; }
+            marcas[(m - baixo) as usize] = false;
+            multiplo = m.checked_add(p);
+         }
+      }
+      // o que sobrou sem marcação é primo.
+      for (k, &primo) in marcas[..n].iter().enumerate() {
+         if primo
+            { primos.push(baixo + k as u64); }
+      }
+
+      if alto == fim
+         { break; }
+      baixo = alto + 1;
+   }
+   primos
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use crate::motor::primos_faixa;
+   use std::time::Instant;
+
+   #[test]
+   fn RaizInteira() {
+      assert_eq!(raiz_inteira(0), 0);
+      assert_eq!(raiz_inteira(15), 3);
+      assert_eq!(raiz_inteira(16), 4);
+      assert_eq!(raiz_inteira(u64::MAX), u32::MAX as u64);
+   }
+
+   #[test]
+   fn FaixasDegeneradas() {
+      assert!(crivo_segmentado(0, 1).is_empty());
+      assert!(crivo_segmentado(20, 10).is_empty());
+      assert_eq!(crivo_segmentado(0, 2), vec![2]);
+      assert_eq!(crivo_segmentado(13, 13), vec![13]);
+      assert!(crivo_segmentado(24, 28).is_empty());
+   }
+
+   #[test]
+   fn MesmosResultadosQueDivisaoPorTentativa() {
+      let faixas = [
+         (1u64, 100u64), (0, 10_000), (15_921, 27_001),
+         // passando pelas bordas dos segmentos.
+         (TAMANHO_SEGMENTO - 50, 3 * TAMANHO_SEGMENTO + 50),
+         (307_991, 418_552)
+      ];
+
+      for (a, b) in faixas {
+         assert_eq!(
+            crivo_segmentado(a, b), primos_faixa(a, b),
+            "faixa {}..={}", a, b
+         );
+      }
+   }
+
+   #[test]
+   fn NumerosGrandes() {
+      let inicio = 10_000_000_000u64;
+      let fim = inicio + 1_000;
+      assert_eq!(crivo_segmentado(inicio, fim), primos_faixa(inicio, fim));
+   }
+
+   #[test]
+   #[ignore="comparação de tempo, depende da carga da máquina"]
+   fn MaisVelozQueDivisaoPorTentativa() {
+      let cronometro = Instant::now();
+      let a = primos_faixa(1, 2_000_000);
+      let t = cronometro.elapsed();
+      let b = crivo_segmentado(1, 2_000_000);
+      let T = cronometro.elapsed() - t;
+
+      assert_eq!(a, b);
+      assert!(T < t);
+   }
+}
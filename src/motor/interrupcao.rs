@@ -0,0 +1,50 @@
+/*!
+ Tratamento dos sinais de interrupção(SIGINT e SIGTERM) durante as
+ varreduras. Ao invés de matar o processo, e perder tudo que foi achado, o
+ sinal apenas levanta uma marcação, que os laços de busca consultam ao fim
+ de cada janela; assim a varredura para num ponto consistente, e o que já
+ foi achado segue o caminho normal de gravação.
+*/
+
+// Bibliotecas externas:
+extern crate libc;
+// Biblioteca do Rust:
+use std::sync::atomic::{AtomicBool, Ordering};
+use libc::{c_int, signal, sighandler_t, SIGINT, SIGTERM, SIG_DFL};
+
+// Se algum sinal de interrupção chegou durante a varredura.
+static INTERROMPIDO: AtomicBool = AtomicBool::new(false);
+
+/* Levanta a marcação de interrupção, como se um sinal tivesse chegado. */
+pub(super) fn marca_interrupcao()
+   { INTERROMPIDO.store(true, Ordering::SeqCst); }
+
+/* Apenas registra o pedido de interrupção. Um segundo sinal já cai no
+ * tratamento padrão, ou seja, mata o processo, para o caso de a varredura
+ * travar por algum motivo. */
+extern "C" fn tratador(sinal: c_int) {
+   marca_interrupcao();
+   unsafe { signal(sinal, SIG_DFL); }
+}
+
+/** Passa a tratar SIGINT e SIGTERM como pedidos de parada da varredura. */
+pub fn captura_interrupcoes() {
+   let funcao = tratador as extern "C" fn(c_int) as sighandler_t;
+   unsafe {
+      signal(SIGINT, funcao);
+      signal(SIGTERM, funcao);
+   }
+}
+
+/** Volta ao tratamento padrão dos sinais, e limpa a marcação. */
+pub fn libera_interrupcoes() {
+   unsafe {
+      signal(SIGINT, SIG_DFL);
+      signal(SIGTERM, SIG_DFL);
+   }
+   INTERROMPIDO.store(false, Ordering::SeqCst);
+}
+
+/** Se foi pedida a interrupção da varredura em andamento. */
+pub fn foi_interrompido() -> bool
+   { INTERROMPIDO.load(Ordering::SeqCst) }
@@ -6,19 +6,15 @@
 extern crate libc;
 // Biblioteca do Rust:
 use std::process::{Stdio, Command};
+use std::io;
 use std::str::FromStr;
 use std::iter::Iterator as I;
 use std::path::Path;
-use std::time::{Duration, Instant};
-use std::ffi::{c_void};
-use std::mem::{size_of, swap};
-use std::mem::{zeroed, transmute};
-use libc::{stat as Stat};
+use std::mem::{swap};
 // Do próprio módulo:
 use crate::motor::{
-   MonitorProcessamento, InfoDeProcessamento, Primos, varre, 
-   divide_intervalo, Intervalo, NOME_DO_PIPE, Processo, 
-   primos_faixa_com_info, primos_faixa
+   MonitorProcessamento, Primos, Intervalo, NOME_DO_PIPE, Processo, 
+   PiscinaDeProcessos, qtd_de_processos, crivo_segmentado
 };
 
 
@@ -102,162 +98,34 @@ fn pipe_name() -> *const i8
 /* Retorna o ponteiro(estilo do C) do do nome do 'named pipe'. */
    { NOME_DO_PIPE.as_ptr() as *const i8 }
 
-fn envia_dados_por_namedpipes(lista: &Vec<u64>)
-{
-/* Envia uma array finita de bytes de cada número inteiro sem sinal de 
- * 64-bits que contém nela. */
-   // Computa o total de bytes a enviar:
-   let total = lista.len() * size_of::<u64>();
-   let pathname = pipe_name();
-   let modo = libc::O_WRONLY;
-   // Ponteiro para 'raw array' da estrutura de lista.
-   let data = lista.as_ptr() as *const c_void;
-
-   unsafe {
-      let fd = libc::open(pathname, modo);
-      let escrito = libc::write(fd, data, total);
-
-      assert_eq!(escrito, total as isize);
-      libc::close(fd);
-   }
-}
+/* Rotina que cada trabalhador aplica no seu pedaço da faixa. */
+fn varre_pedaco(i: Intervalo) -> Vec<u64>
+   { crivo_segmentado(*i.start(), *i.end()) }
 
-fn encontra_primos_e_envia_via_named_pipes(i: &Intervalo) -> Primos
-{
-   if cfg!(debug_assertions) 
-      { println!("\tPrimos buscados em {:?}", *i); }
-
-   // Realiza varredura para encontrar primos.
-   let mut output = varre(i.clone());
-   assert!(output.len() > 0);
-   let lista = output.drain().collect::<Vec<u64>>();
-
-   envia_dados_por_namedpipes(&lista);
-   // Lista que serve apenas como buffer pode ser liberada agora.
-   drop(lista);
-
-   // Informando saída ...
-   if cfg!(debug_assertions) 
-      { println!("\t\tVarredura de {:?} finalizada", *i); }
-
-   // Pensando no processo pai, retorna o conteúdo encontrado aqui.
-   output
-}
-
-fn recebe_primos_um_por_um_via_named_pipes(out: &mut Primos) {
-/* Captura bytes de cada inteiro sem sinal de 64-bits que está sendo enviado
- * via 'named pipes'. Colocas num 'conjunto' de primos. */
-   const N: usize = size_of::<u64>();
-   let mut buffer: [u8; N] = [u8::MAX; N];
-   let data = buffer.as_mut_ptr() as *mut c_void;
-   let modo = libc::O_RDONLY;
-   let mut clock = Instant::now();
-   let limite = Duration::from_micros(500); 
-
-   unsafe {
-      let via = libc::open(pipe_name(), modo);
-
-      // Cada primo lido vai sendo adicionado ao conjunto de primos ...
-      'leitura: loop { 
-         let lido = libc::read(via, data, N);
-
-         if clock.elapsed() > limite {
-            let mut mt: libc::stat = zeroed(); 
-            let ptr = transmute::<&mut Stat, *mut Stat>(&mut mt);
-
-            libc::fstat(via, ptr);
-            println!("bytes: {buffer:?}, lido: {lido:}");
-            println!("Ainda no buffer: {} bytes", mt.st_blocks);
-            clock = Instant::now();
-         }
-
-         if lido == N as isize
-            { out.insert(u64::from_le_bytes(buffer)); }
-
-         if lido == 0
-            { break 'leitura; }
-      }
-      libc::close(via);
-   }
-}
-
-fn varre_em_paralelo(i: Intervalo) -> Primos 
+pub fn varre_em_paralelo(i: Intervalo) -> io::Result<Primos> 
 {
 /* Faz o processamento via multiplos processos; isso tudo via uma chamada
  * de sistema, o 'fork'. Este método de apelar multiplos processo faz com
  * que todo recurso do CPU seja utilizado. O processamento de primos em seu
- * máximo. */
-   let ranges = divide_intervalo(i, 4);
-   let rotine: fn(&Intervalo) -> Primos;
-   let sucesso = libc::EXIT_SUCCESS;
-   let mut output: Primos;
-
-   // Atribuido aqui pra não transbordar a tela.
-   rotine = encontra_primos_e_envia_via_named_pipes;
-
-   /* Começando a forquear subprocessos para processar seus respectivos
-    * intervalos dados. Neste caso aqui, estamos falando de 4 forques,
-    * pois são quatro CPUs ao todo na máquina. */
-   unsafe {
-      if libc::fork() == 0 {
-         rotine(&ranges[0]); 
-         libc::exit(sucesso); 
-      } else { 
-         if libc::fork() == 0 {
-            rotine(&ranges[1]);
-            libc::exit(sucesso); 
-         } else {
-            if libc::fork() == 0 {
-               rotine(&ranges[2]);
-               libc::exit(sucesso); 
-            }   
-            output = varre(ranges[3].clone()); 
-         }
-      }
-   }
-   
-   /* Recebendo os primos enviados, então juntando com os demais. No fim,
-    * apenas retorna o resultado encontrado. */
-   recebe_primos_um_por_um_via_named_pipes(&mut output);
-   output
+ * máximo. A quantia de forques é uma por CPU, ou a pedida via argumento.*/
+/* Se algum trabalhador falhar, a falha é repassada a quem chamou. */
+   let piscina = PiscinaDeProcessos::nova(qtd_de_processos());
+   piscina.varre(i, varre_pedaco)
 }
 
 pub fn varre_em_paralelo_com_info
-  (faixa: Intervalo, info: MonitorProcessamento) -> Primos 
+  (faixa: Intervalo, info: MonitorProcessamento) -> io::Result<Primos> 
 {
-   let ranges = divide_intervalo(faixa, 4);
-   let rotine: fn(&Intervalo) -> Primos;
-   let sucesso = libc::EXIT_SUCCESS;
-   let mut output: Primos;
-
-   // Atribuido aqui pra não transbordar a tela.
-   rotine = encontra_primos_e_envia_via_named_pipes;
-
-   /* Começando a forquear subprocessos para processar seus respectivos
-    * intervalos dados. Neste caso aqui, estamos falando de 4 forques,
-    * pois são quatro CPUs ao todo na máquina. */
-   unsafe {
-      if libc::fork() == 0 {
-         rotine(&ranges[0]); 
-         libc::exit(sucesso); 
-      } else { 
-         if libc::fork() == 0 {
-            rotine(&ranges[1]);
-            libc::exit(sucesso); 
-         } else {
-            if libc::fork() == 0 {
-               rotine(&ranges[2]);
-               libc::exit(sucesso); 
-            }   
-            output = varre(ranges[3].clone()); 
-         }
-      }
+   let output = varre_em_paralelo(faixa)?;
+
+   // Registrando o que foi feito, se pedido.
+   if let Some(info) = info {
+      info.percentual = 1.0;
+      info.quantia = output.len();
+      if !output.is_empty() 
+         { info.tempo = info.cronometro.elapsed() / output.len() as u32; }
    }
-   
-   /* Recebendo os primos enviados, então juntando com os demais. No fim,
-    * apenas retorna o resultado encontrado. */
-   recebe_primos_um_por_um_via_named_pipes(&mut output);
-   output
+   Ok(output)
 }
 
 
@@ -267,6 +135,9 @@ mod tests {
 
    use super::*;
    use std::env::args;
+   use std::ffi::{c_void};
+   use std::mem::{size_of};
+   use crate::motor::InfoDeProcessamento;
 
    #[test]
    fn testeBasicoFI() {
@@ -335,7 +206,7 @@ mod tests {
 
    #[test]
    fn construcaodeVarreduraViaForque() {
-      let output = varre_em_paralelo(1..=5_000);
+      let output = varre_em_paralelo(1..=5_000).unwrap();
 
       println!("Primos encontrados em até 1000: {}", output.len());
       lista_todos_primos(&output); 
@@ -345,6 +216,6 @@ mod tests {
    fn processamentoRealmenteBastantesPrimos() {
       let faixa = 1..=10_000_000; 
       let mut info = InfoDeProcessamento::nova();
-      let out = varre_em_paralelo_com_info(faixa, Some(&mut info));
+      let out = varre_em_paralelo_com_info(faixa, Some(&mut info)).unwrap();
    }
 }
@@ -0,0 +1,363 @@
+/*!
+ Piscina de processos para varrer uma faixa grande. Ao invés de quebrar a
+ faixa em uma parte fixa por forque, ela é fatiada em vários pedaços
+ menores, que vão sendo entregues a cada trabalhador assim que este termina
+ o anterior; assim quem pegou uma parte mais custosa não segura os demais.
+
+ Cada trabalhador é um forque com dois canos(pipes) próprios: num recebe
+ os pedaços(dois inteiros, o ínicio e o fim), no outro devolve o que achou
+ (a quantia, seguida dos primos). Um trabalhador que morre no meio é
+ notado pelo fim inesperado do seu cano, e reportado como erro, ao invés de
+ deixar o processo pai esperando para sempre.
+*/
+
+// Bibliotecas externas:
+extern crate libc;
+// Biblioteca do Rust:
+use std::fs::File;
+use std::io::{self, Read, Write, Error, ErrorKind};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::panic::catch_unwind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use libc::{c_int, pid_t, pollfd, POLLIN};
+// Do próprio módulo:
+use crate::motor::{Intervalo, Primos, junta_ordenados};
+
+// Em quantos pedaços, por trabalhador, a faixa é fatiada.
+const PEDACOS_POR_TRABALHADOR: u64 = 8;
+// Menor pedaço que vale a pena mandar para outro processo.
+const PEDACO_MINIMO: u64 = 10_000;
+/* Quantia de processos definida pelo usuário, zero é deixar a cargo da
+ * quantia de CPUs da máquina. */
+static QTD_DE_PROCESSOS: AtomicUsize = AtomicUsize::new(0);
+
+/** Fixa a quantia de processos usada pelas varreduras paralelas. */
+pub fn define_qtd_de_processos(qtd: usize)
+   { QTD_DE_PROCESSOS.store(qtd, Ordering::SeqCst); }
+
+/** Quantia de processos que as varreduras paralelas usam: a definida pelo
+ usuário, ou uma por CPU. */
+pub fn qtd_de_processos() -> usize {
+   match QTD_DE_PROCESSOS.load(Ordering::SeqCst) {
+      0 => num_cpus::get(),
+      qtd => qtd
+   }
+}
+
+/* Um dos forques da piscina, com as pontas que ficam com o pai. */
+struct Trabalhador {
+   pid: pid_t,
+   // por onde os pedaços são enviados.
+   tarefas: File,
+   // por onde os primos achados voltam.
+   resultados: File,
+   // pedaço que está processando agora, se algum.
+   atual: Option<Intervalo>
+}
+
+/* Cria um cano, retornando a ponta de leitura e a de escrita. */
+fn cria_cano() -> io::Result<(File, File)> {
+   let mut fds: [c_int; 2] = [-1; 2];
+
+   if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1
+      { return Err(Error::last_os_error()); }
+   unsafe { Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))) }
+}
+
+/* Lê um pedaço do cano, nada se este foi fechado(fim do trabalho). */
+fn le_pedaco(cano: &mut File) -> io::Result<Option<Intervalo>> {
+   let mut bytes = [0u8; 16];
+
+   match cano.read_exact(&mut bytes) {
+      Ok(_) => {
+         let mut a = [0u8; 8];
+         let mut b = [0u8; 8];
+         a.copy_from_slice(&bytes[0..8]);
+         b.copy_from_slice(&bytes[8..16]);
+         Ok(Some(u64::from_le_bytes(a)..=u64::from_le_bytes(b)))
+      } Err(ref erro) if erro.kind() == ErrorKind::UnexpectedEof =>
+         { Ok(None) }
+      Err(erro) => Err(erro)
+   }
+}
+
+/* Laço de vida do processo filho: processa pedaço por pedaço, até que o
+ * pai feche o cano de tarefas. Nunca retorna. */
+fn trabalha(mut tarefas: File, mut resultados: File,
+  rotina: fn(Intervalo) -> Vec<u64>) -> !
+{
+   let codigo = catch_unwind(move || {
+      while let Some(pedaco) = le_pedaco(&mut tarefas)? {
+         let primos = rotina(pedaco);
+         let mut bytes = Vec::with_capacity(8 * (primos.len() + 1));
+
+         bytes.extend((primos.len() as u64).to_le_bytes());
+         for p in primos
+            { bytes.extend(p.to_le_bytes()); }
+         resultados.write_all(&bytes[..])?;
+      }
+      Ok::<(), Error>(())
+   });
+
+   // saída "crua", para não rodar nada herdado do processo pai.
+   unsafe {
+      match codigo {
+         Ok(Ok(_)) => libc::_exit(libc::EXIT_SUCCESS),
+         Ok(Err(_)) => libc::_exit(libc::EXIT_FAILURE),
+         Err(_) => libc::_exit(101)
+      }
+   }
+}
+
+impl Trabalhador {
+   /* Forqueia um novo trabalhador. Os já existentes são passados para que
+    * o filho feche as pontas deles que herdou; do contrário, o fim dos
+    * canos de um trabalhador morto nunca seria notado. */
+   fn lanca(rotina: fn(Intervalo) -> Vec<u64>,
+     outros: &mut Vec<Trabalhador>) -> io::Result<Trabalhador>
+   {
+      let (le_tarefa, escreve_tarefa) = cria_cano()?;
+      let (le_resultado, escreve_resultado) = cria_cano()?;
+
+      match unsafe { libc::fork() } {
+         -1 => Err(Error::last_os_error()),
+         0 => {
+            outros.clear();
+            drop(escreve_tarefa);
+            drop(le_resultado);
+            trabalha(le_tarefa, escreve_resultado, rotina)
+         } pid => {
+            drop(le_tarefa);
+            drop(escreve_resultado);
+            Ok(Trabalhador {
+               pid, tarefas: escreve_tarefa,
+               resultados: le_resultado, atual: None
+            })
+         }
+      }
+   }
+
+   fn envia(&mut self, pedaco: Intervalo) -> io::Result<()> {
+      let mut bytes = [0u8; 16];
+      bytes[0..8].copy_from_slice(&pedaco.start().to_le_bytes());
+      bytes[8..16].copy_from_slice(&pedaco.end().to_le_bytes());
+      self.tarefas.write_all(&bytes[..])?;
+      self.atual = Some(pedaco);
+      Ok(())
+   }
+
+   fn recebe(&mut self) -> io::Result<Vec<u64>> {
+      let mut bytes = [0u8; 8];
+      self.resultados.read_exact(&mut bytes)?;
+      let qtd = u64::from_le_bytes(bytes) as usize;
+      let mut primos = vec![0u8; 8 * qtd];
+      self.resultados.read_exact(&mut primos[..])?;
+      self.atual = None;
+
+      Ok(primos.chunks_exact(8).map(|c| {
+         bytes.copy_from_slice(c);
+         u64::from_le_bytes(bytes)
+      }).collect())
+   }
+
+   /* Recolhe o processo(já morto) e descreve o porquê da falha. */
+   fn falha(&self) -> Error {
+      let mut status: c_int = 0;
+      let causa = unsafe {
+         if libc::waitpid(self.pid, &mut status, 0) == -1
+            { String::from("sumiu") }
+         else if libc::WIFSIGNALED(status)
+            { format!("foi morto pelo sinal {}", libc::WTERMSIG(status)) }
+         else
+            { format!("saiu com código {}", libc::WEXITSTATUS(status)) }
+      };
+      let msg = format!(
+         "trabalhador(pid {}) {}, processando {:?}",
+         self.pid, causa, self.atual
+      );
+      Error::new(ErrorKind::Other, msg)
+   }
+}
+
+/** Fatia a faixa em pedaços de no máximo o tamanho dado. */
+pub fn fatia(faixa: Intervalo, tamanho: u64) -> Vec<Intervalo> {
+   let mut pedacos = Vec::new();
+   let (mut a, fim) = (*faixa.start(), *faixa.end());
+
+   while a <= fim {
+      let b = a.saturating_add(tamanho - 1).min(fim);
+      pedacos.push(a..=b);
+      match b.checked_add(1) {
+         Some(proximo) => a = proximo,
+         None => break
+      };
+   }
+   pedacos
+}
+
+/* Tamanho de cada pedaço da faixa, para que cada trabalhador pegue alguns
+ * deles. Em 128-bits, pois a faixa inteira dos 64-bits tem 2⁶⁴ números. */
+fn tamanho_do_pedaco(faixa: &Intervalo, trabalhadores: usize) -> u64 {
+   let total = *faixa.end() as u128 - *faixa.start() as u128 + 1;
+   let partes = trabalhadores as u128 * PEDACOS_POR_TRABALHADOR as u128;
+   let tamanho = (total / partes).min(u64::MAX as u128) as u64;
+   tamanho.max(PEDACO_MINIMO)
+}
+
+/** Piscina com uma quantia fixa de processos trabalhadores. */
+pub struct PiscinaDeProcessos { trabalhadores: usize }
+
+impl PiscinaDeProcessos {
+   /** Piscina com a quantia de trabalhadores dada, ou com a padrão do
+    programa(ver `qtd_de_processos`), se for zero. */
+   pub fn nova(trabalhadores: usize) -> Self {
+      let trabalhadores = {
+         if trabalhadores == 0
+            { qtd_de_processos() }
+         else
+            { trabalhadores }
+      };
+      PiscinaDeProcessos { trabalhadores }
+   }
+
+   /** Aplica a 'rotina' em toda a faixa, repartida entre os trabalhadores,
+    juntando tudo que foi achado(já em ordem). Se algum trabalhador morrer, os demais
+    são derrubados, e a falha é retornada. */
+   pub fn varre(&self, faixa: Intervalo, rotina: fn(Intervalo) -> Vec<u64>)
+     -> io::Result<Primos>
+   {
+      let tamanho = tamanho_do_pedaco(&faixa, self.trabalhadores);
+      let mut pedacos = fatia(faixa, tamanho).into_iter();
+      let qtd = self.trabalhadores.min(pedacos.len());
+      let mut trabalhadores = Vec::<Trabalhador>::with_capacity(qtd);
+      let mut partes = Vec::<Primos>::new();
+
+      for _ in 0..qtd {
+         match Trabalhador::lanca(rotina, &mut trabalhadores) {
+            Ok(t) => trabalhadores.push(t),
+            Err(erro) =>
+               { encerra(trabalhadores, true); return Err(erro); }
+         };
+      }
+      let resultado = distribui(&mut trabalhadores, &mut pedacos, &mut partes);
+      encerra(trabalhadores, resultado.is_err());
+      // os pedaços voltam na ordem em que terminaram, não na da faixa.
+      resultado.map(|_| junta_ordenados(partes))
+   }
+}
+
+/* Entrega os pedaços para quem estiver livre, e colhe os resultados, até
+ * que não sobre nenhum pedaço, nem trabalhador ocupado. */
+fn distribui(trabalhadores: &mut [Trabalhador],
+  pedacos: &mut impl Iterator<Item=Intervalo>, partes: &mut Vec<Primos>)
+  -> io::Result<()>
+{
+   // primeira leva, um pedaço para cada.
+   for t in trabalhadores.iter_mut() {
+      if let Some(pedaco) = pedacos.next()
+         { t.envia(pedaco).map_err(|_| t.falha())?; }
+   }
+
+   loop {
+      let ocupados: Vec<usize> = {
+         (0..trabalhadores.len())
+         .filter(|&k| trabalhadores[k].atual.is_some())
+         .collect()
+      };
+      if ocupados.is_empty()
+         { return Ok(()); }
+
+      let mut canos: Vec<pollfd> = ocupados.iter().map(|&k| {
+         let fd = trabalhadores[k].resultados.as_raw_fd();
+         pollfd { fd, events: POLLIN, revents: 0 }
+      }).collect();
+      let n = canos.len() as libc::nfds_t;
+
+      if unsafe { libc::poll(canos.as_mut_ptr(), n, -1) } == -1 {
+         let erro = Error::last_os_error();
+         if erro.kind() == ErrorKind::Interrupted
+            { continue; }
+         return Err(erro);
+      }
+
+      for (cano, &k) in canos.iter().zip(ocupados.iter()) {
+         if cano.revents == 0
+            { continue; }
+         let t = &mut trabalhadores[k];
+         // o fim do cano antes do resultado: o trabalhador morreu.
+         partes.push(t.recebe().map_err(|_| t.falha())?);
+
+         if let Some(pedaco) = pedacos.next()
+            { t.envia(pedaco).map_err(|_| t.falha())?; }
+      }
+   }
+}
+
+/* Fecha os canos de tarefas(o que finaliza os trabalhadores), ou os mata
+ * de vez, e então recolhe todos processos. */
+fn encerra(trabalhadores: Vec<Trabalhador>, mata: bool) {
+   for t in trabalhadores {
+      let mut status: c_int = 0;
+      let pid = t.pid;
+
+      drop(t);
+      unsafe {
+         if mata
+            { libc::kill(pid, libc::SIGKILL); }
+         libc::waitpid(pid, &mut status, 0);
+      }
+   }
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+   use super::*;
+   use crate::motor::crivo_segmentado;
+
+   fn crivo(faixa: Intervalo) -> Vec<u64>
+      { crivo_segmentado(*faixa.start(), *faixa.end()) }
+
+   fn falha_no_meio(faixa: Intervalo) -> Vec<u64> {
+      if faixa.contains(&150_000)
+         { panic!("falha proposital"); }
+      crivo(faixa)
+   }
+
+   #[test]
+   fn Fatia() {
+      assert_eq!(fatia(1..=10, 4), vec![1..=4, 5..=8, 9..=10]);
+      assert_eq!(fatia(5..=5, 4), vec![5..=5]);
+      let fim = u64::MAX;
+      assert_eq!(fatia((fim - 2)..=fim, 2), vec![(fim-2)..=(fim-1), fim..=fim]);
+   }
+
+   #[test]
+   fn PedacosDaFaixaInteira() {
+      let partes = PEDACOS_POR_TRABALHADOR as u128;
+      let esperado = ((1u128 << 64) / partes) as u64;
+      assert_eq!(tamanho_do_pedaco(&(0..=u64::MAX), 1), esperado);
+      assert_eq!(tamanho_do_pedaco(&(5..=5), 8), PEDACO_MINIMO);
+   }
+
+   #[test]
+   fn MesmosResultadosQueOCrivo() {
+      let esperado = crivo(1..=500_000);
+
+      for qtd in [1, 3, 7] {
+         let piscina = PiscinaDeProcessos::nova(qtd);
+         assert_eq!(piscina.varre(1..=500_000, crivo).unwrap(), esperado);
+      }
+   }
+
+   #[test]
+   fn TrabalhadorQueFalhaEReportado() {
+      let piscina = PiscinaDeProcessos::nova(3);
+      let resultado = piscina.varre(1..=500_000, falha_no_meio);
+      let erro = resultado.unwrap_err();
+
+      println!("{}", erro);
+      assert!(erro.to_string().contains("101"));
+   }
+}
@@ -1,24 +1,50 @@
 
 use std::ops::RangeInclusive;
-use std::collections::HashSet;
-use std::thread::{spawn, JoinHandle};
+use std::collections::{HashSet, VecDeque, BinaryHeap};
+use std::cmp::Reverse;
+use std::sync::Mutex;
+use std::thread::{scope, JoinHandle};
 use crate::motor::{
    MonitorProcessamento, primos_faixa, primos_faixa_com_info, Intervalo,
-   Primos, Intervalos, Fios,
+   Primos, Intervalos, fatia
 };
 
+// Em quantos pedaços, por thread, a faixa é fatiada.
+const PEDACOS_POR_FIO: u64 = 16;
+// Fila de pedaços de cada thread, os outros podem roubar dela.
+type Fila = Mutex<VecDeque<Intervalo>>;
 
-/* Embrulho da função 'primos_faixa', que retorna a array de u64 como um 
- * conjunto do mesmo. */
+
+/* Embrulho da função 'primos_faixa', que recebe o intervalo ao invés dos
+ * seus limites. */
+#[cfg(test)]
 pub fn varre(intervalo: Intervalo) -> Primos {
    let a = *intervalo.start();
    let b = * intervalo.end();
-   let mut array = primos_faixa(a, b);
-   let mut conjunto = Primos::with_capacity(array.len());
+   primos_faixa(a, b)
+}
+
+/** Junta várias listas ordenadas(os resultados de cada pedaço, de cada 
+ thread ou processo) numa só, também ordenada, e sem repetições. Não 
+ importa em qual ordem as listas chegaram. */
+pub fn junta_ordenados(partes: Vec<Vec<u64>>) -> Primos {
+   let total = partes.iter().map(Vec::len).sum();
+   let mut juncao = Primos::with_capacity(total);
+   // o menor valor ainda não juntado de cada lista, e de onde ele é.
+   let mut proximos = BinaryHeap::with_capacity(partes.len());
+
+   for (k, parte) in partes.iter().enumerate() {
+      if let Some(&primeiro) = parte.first()
+         { proximos.push(Reverse((primeiro, k, 0))); }
+   }
 
-   for e in array.drain(..)
-      { conjunto.insert(e); }
-   conjunto
+   while let Some(Reverse((valor, k, posicao))) = proximos.pop() {
+      if juncao.last() != Some(&valor)
+         { juncao.push(valor); }
+      if let Some(&seguinte) = partes[k].get(posicao + 1)
+         { proximos.push(Reverse((seguinte, k, posicao + 1))); }
+   }
+   juncao
 }
 
 /* Gera vários intervalos disjuntos, dado os limites. */
@@ -36,39 +62,73 @@ fn intervalos_de(mut i: u64, f: u64, qtd: u64) -> Intervalos {
       i += q;
    }
 
-   lista.push((i+1)..=f);
+   // se foi só um, o ínicio não pode ficar de fora.
+   if primeiro 
+      { lista.push((i+1)..=f); }
+   else
+      { lista.push(i..=f); }
    lista
 }
 
-/* Pega o intervalo dado, faz repartições, e busca cada fatia usando da 
- * técnica de concorrência, com isso fica em média duas vezes mais veloz 
- * do que a técnica antiga. Os parâmetros são o intervalo repartido 'i',
- * e 'nt' é a quantia de threads utilizadas, mais de quartoze, o efeito é 
- * basicamente o mesmo ou menor, então modere.
- */
-pub fn simultaneadade(i: Intervalo, nt: usize) -> Primos {
-   let a = *i.start();
-   let b = *i.end();
-   let mut conjunto = Primos::with_capacity(1000);
-   let mut fios = Fios::with_capacity(nt);
-
-   // criando threads ...
-   let d = nt as u64;
-   for intervalo in intervalos_de(a, b, d).drain(..) {
-      let fio = spawn(|| { varre(intervalo) });
-      fios.push(fio);
-   }
+/* Próximo pedaço para a thread 'dono': o primeiro da própria fila, ou, se
+ * esta já esvaziou, o último da fila de alguma outra(o roubo). Nada se
+ * todas filas estiverem vazias, como nenhum pedaço novo é criado durante a
+ * varredura, aí o trabalho acabou. */
+fn pega_pedaco(filas: &[Fila], dono: usize) -> Option<Intervalo> {
+   if let Some(pedaco) = filas[dono].lock().unwrap().pop_front()
+      { return Some(pedaco); }
 
-   // aguardando todas terminar ...
-   for f in fios.drain(..) {
-      match f.join() {
-         Ok(set) => 
-            { conjunto.extend(set); }
-         Err(_) =>
-            { panic!("não funcionou para tal 'fio'."); }
-      };
+   for k in 1..filas.len() {
+      let vitima = (dono + k) % filas.len();
+      if let Some(pedaco) = filas[vitima].lock().unwrap().pop_back()
+         { return Some(pedaco); }
    }
-   conjunto
+   None
+}
+
+/* Busca primos no intervalo 'i' usando 'nt' threads. A faixa é fatiada em
+ * vários pedaços pequenos, e cada thread começa com um bloco contíguo
+ * deles; a que terminar o seu bloco rouba os pedaços finais(os mais 
+ * custosos) das outras. Assim todas terminam mais ou menos juntas. */
+pub fn simultaneadade(i: Intervalo, nt: usize) -> Primos {
+   let nt = nt.max(1);
+   let total = i.end().saturating_sub(*i.start()) + 1;
+   let tamanho = (total / (nt as u64 * PEDACOS_POR_FIO)).max(1);
+   let pedacos = fatia(i, tamanho);
+   let por_fio = (pedacos.len() + nt - 1) / nt;
+   // distribuindo os pedaços em blocos contíguos, um para cada thread.
+   let filas: Vec<Fila> = {
+      pedacos.chunks(por_fio.max(1))
+      .map(|bloco| Mutex::new(bloco.iter().cloned().collect()))
+      .collect()
+   };
+   let mut partes = Vec::<Primos>::with_capacity(pedacos.len());
+
+   scope(|s| {
+      let fios: Vec<_> = (0..filas.len()).map(|dono| {
+         let filas = &filas;
+         s.spawn(move || {
+            // cada pedaço já sai ordenado, só a junção final que não.
+            let mut achados = Vec::<Primos>::new();
+            while let Some(pedaco) = pega_pedaco(filas, dono) {
+               let (a, b) = (*pedaco.start(), *pedaco.end());
+               achados.push(primos_faixa(a, b));
+            }
+            achados
+         })
+      }).collect();
+
+      // aguardando todas terminar ...
+      for f in fios {
+         match f.join() {
+            Ok(listas) => 
+               { partes.extend(listas); }
+            Err(_) =>
+               { panic!("não funcionou para tal 'fio'."); }
+         };
+      }
+   });
+   junta_ordenados(partes)
 }
 
 /* O mesmo que o intervalo_de, no entanto, ele faz com um intervalo 
@@ -90,6 +150,41 @@ mod tests {
    use super::*;
    use std::time::{Instant};
    use utilitarios::tabelas::{Tabela, Coluna};
+   use std::thread::spawn;
+   use crate::motor::Fios;
+
+   /* Pega o intervalo dado, faz repartições, e busca cada fatia usando da 
+    * técnica de concorrência, com isso fica em média duas vezes mais veloz 
+    * do que a técnica antiga. Os parâmetros são o intervalo repartido 'i',
+    * e 'nt' é a quantia de threads utilizadas, mais de quartoze, o efeito é 
+    * basicamente o mesmo ou menor, então modere. Como cada thread fica com
+    * uma fatia fixa, as que pegam os números maiores sempre terminam por
+    * último; ficou apenas para comparação com a 'simultaneadade'.
+    */
+   fn simultaneadade_estatica(i: Intervalo, nt: usize) -> Primos {
+      let a = *i.start();
+      let b = *i.end();
+      let mut partes = Vec::<Primos>::with_capacity(nt);
+      let mut fios = Fios::with_capacity(nt);
+
+      // criando threads ...
+      let d = nt as u64;
+      for intervalo in intervalos_de(a, b, d).drain(..) {
+         let fio = spawn(|| { varre(intervalo) });
+         fios.push(fio);
+      }
+
+      // aguardando todas terminar ...
+      for f in fios.drain(..) {
+         match f.join() {
+            Ok(lista) => 
+               { partes.push(lista); }
+            Err(_) =>
+               { panic!("não funcionou para tal 'fio'."); }
+         };
+      }
+      junta_ordenados(partes)
+   }
 
    #[test]
    fn IntervalosDe() {
@@ -165,4 +260,75 @@ mod tests {
       // avaliação manual:
       assert!(true);
    }
+
+   #[test]
+   fn RouboDeTrabalhoMesmoResultado() {
+      for (faixa, nt) in [(2..=27_000, 7), (1..=10, 4), (5..=5, 3)] {
+         assert_eq!(
+            simultaneadade(faixa.clone(), nt),
+            simultaneadade_estatica(faixa.clone(), nt.min(3)),
+            "faixa {:?} com {} threads", faixa, nt
+         );
+      }
+      assert_eq!(simultaneadade(2..=27_000, 7), varre(2..=27_000));
+   }
+
+   #[test]
+   #[ignore="comparação de tempo, depende da carga da máquina"]
+   fn RouboDeTrabalhoContraFatiasFixas() {
+      let total: u64 = 3_000_000;
+      let mut n_threads: Vec<usize> = Vec::with_capacity(8);
+      let mut ganhos: Vec<f32> = Vec::with_capacity(8);
+      let (mut estatica, mut roubo) = (0.0_f32, 0.0_f32);
+
+      for nt in [2, 4, 8, 12] {
+         let cronometro = Instant::now();
+         let l1 = simultaneadade_estatica(2..=total, nt);
+         let t1 = cronometro.elapsed();
+         let l2 = simultaneadade(2..=total, nt);
+         let t2 = cronometro.elapsed() - t1;
+
+         assert_eq!(l1, l2);
+         n_threads.push(nt);
+         // quantas vezes o roubo foi mais veloz que as fatias fixas.
+         ganhos.push(t1.as_secs_f32() / t2.as_secs_f32());
+         estatica += t1.as_secs_f32();
+         roubo += t2.as_secs_f32();
+      }
+
+      // visualizando informação.
+      let fios = Coluna::nova("qtd. de threads", n_threads);
+      let ganho = Coluna::nova("ganho do roubo", ganhos);
+      let mut tabela = Tabela::nova(true);
+
+      tabela.adiciona(fios);
+      tabela.adiciona(ganho);
+      println!("{}", tabela);
+
+      /* o ganho depende da quantia de CPUs da máquina(avaliação manual),
+       * mas no total o roubo de trabalho não pode sair muito perdendo. */
+      println!("fatias fixas: {:.3}s, roubo: {:.3}s", estatica, roubo);
+      assert!(roubo <= 1.25 * estatica);
+   }
+
+   #[test]
+   fn JuntaOrdenados() {
+      let partes = vec![
+         vec![23, 29, 31], vec![], vec![2, 3, 5, 7],
+         vec![7, 11, 13], vec![17, 19, 23]
+      ];
+      assert_eq!(
+         junta_ordenados(partes),
+         vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31]
+      );
+      assert!(junta_ordenados(Vec::new()).is_empty());
+   }
+
+   #[test]
+   fn ResultadosOrdenados() {
+      let esperado = primos_faixa(1, 50_000);
+      assert_eq!(simultaneadade(1..=50_000, 6), esperado);
+      assert_eq!(simultaneadade_estatica(1..=50_000, 6), esperado);
+      assert_eq!(divide_intervalo(1..=10, 1), vec![1..=10]);
+   }
 }